@@ -3,13 +3,28 @@ use quote::quote;
 
 /// See the `fn call` docs at the `lib.rs` of this crate for a high level definition.
 pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
-	let CallDef { pallet_struct, methods } = def;
+	let CallDef { pallet_struct, generics, methods } = def;
+	let (impl_generics, ty_generics, _) = generics.split_for_impl();
+	let where_predicates =
+		generics.where_clause.iter().flat_map(|where_clause| &where_clause.predicates).collect::<Vec<_>>();
+
+	// The `Call` enum takes the same parameters as the pallet. The instance parameter `I` of an
+	// instantiable pallet defaults to `()`, like on the pallet struct.
+	let mut enum_generics = generics.clone();
+	for param in enum_generics.type_params_mut() {
+		if param.ident == "I" && param.default.is_none() {
+			param.eq_token = Some(Default::default());
+			param.default = Some(syn::parse_quote!(()));
+		}
+	}
+	let enum_params = enum_generics.params.iter();
+	let type_params = generics.type_params().map(|param| &param.ident).collect::<Vec<_>>();
 
 	// This is a vector of all the callable function names.
 	let fn_name = methods.iter().map(|method| &method.name).collect::<Vec<_>>();
 
 	// This is a nested vector of all the arguments for each of the functions in `fn_name`. It does
-	// not include the `self` or `caller: T::AccountId` parameter, which we always assume are the
+	// not include the `self` or `origin: T::RuntimeOrigin` parameter, which we always assume are the
 	// first two parameters to these calls.
 	let args_name = methods
 		.iter()
@@ -23,42 +38,164 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		.map(|method| method.args.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>())
 		.collect::<Vec<_>>();
 
+	// This is the weight expression of each call, which may refer to its arguments.
+	let weight = methods.iter().map(|method| &method.weight).collect::<Vec<_>>();
+
+	// This is the index of each call, as declared by its `#[call_index(..)]` attribute.
+	let call_index = methods.iter().map(|method| method.index).collect::<Vec<_>>();
+
+	// Whether each call may be made by an unsigned extrinsic, as declared by `#[allow_unsigned]`.
+	let allow_unsigned = methods.iter().map(|method| method.allow_unsigned).collect::<Vec<_>>();
+
+	// These are the names of the types of the arguments, and the doc comments of each function, as
+	// they are described in the metadata.
+	let args_type_name = args_type
+		.iter()
+		.map(|types| types.iter().map(|type_| crate::metadata::type_name(type_)).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let docs = methods.iter().map(|method| &method.docs).collect::<Vec<_>>();
+
+	// This is a flat list of the types of all arguments, used to bound the codec implementations.
+	let all_args_type = args_type.iter().flatten().collect::<Vec<_>>();
+
 	// This quote block creates an `enum Call` which contains all the calls exposed by our pallet,
-	// and the `Dispatch` trait logic to route a `caller` to access those functions.
+	// and the `Dispatch` trait logic to route an `origin` to access those functions.
 	let dispatch_impl = quote! {
 		// The callable functions exposed by this pallet.
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
+		//
+		// The hidden variant uses `T` even when the pallet has no calls, and can never be built.
 		#[allow(non_camel_case_types)]
-		pub enum Call<T: Config> {
+		#[derive(Debug, macros::CloneNoBound)]
+		pub enum Call<#( #enum_params ),*>
+		where
+			#( #where_predicates, )*
+		{
 			#(
 				#fn_name { #( #args_name: #args_type),* },
 			)*
+			#[doc(hidden)]
+			__Ignore(core::marker::PhantomData<( #( #type_params, )* )>, core::convert::Infallible),
+		}
+
+		// Encode the index of the call followed by its arguments.
+		impl #impl_generics crate::codec::Encode for Call #ty_generics
+		where
+			#( #where_predicates, )*
+			#( #all_args_type: crate::codec::Encode, )*
+		{
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							dest.push(#call_index);
+							#( crate::codec::Encode::encode_to(#args_name, dest); )*
+						},
+					)*
+					Call::__Ignore(_, never) => match *never {},
+				}
+			}
+		}
+
+		// Decode the index of the call, and then the arguments of that call.
+		impl #impl_generics crate::codec::Decode for Call #ty_generics
+		where
+			#( #where_predicates, )*
+			#( #all_args_type: crate::codec::Decode, )*
+		{
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+				match <u8 as crate::codec::Decode>::decode(input)? {
+					#(
+						#call_index => Ok(Call::#fn_name {
+							#( #args_name: <#args_type as crate::codec::Decode>::decode(input)?, )*
+						}),
+					)*
+					_ => Err(crate::codec::Error::InvalidVariant),
+				}
+			}
+		}
+
+		// The weight of each call, computed from the expression in its `#[weight(..)]` attribute, and
+		// whether it has an `#[allow_unsigned]` attribute.
+		impl #impl_generics crate::support::GetDispatchInfo for Call #ty_generics
+		where
+			#( #where_predicates, )*
+		{
+			// Not every weight expression uses all the arguments of its call.
+			#[allow(unused_variables)]
+			fn get_dispatch_info(&self) -> crate::support::DispatchInfo {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							crate::support::DispatchInfo { weight: #weight, allow_unsigned: #allow_unsigned }
+						},
+					)*
+					Call::__Ignore(_, never) => match *never {},
+				}
+			}
+		}
+
+		// Describe every call of the pallet, for the metadata of the runtime.
+		impl #impl_generics Call #ty_generics
+		where
+			#( #where_predicates, )*
+		{
+			pub fn metadata() -> Vec<crate::metadata::CallMetadata> {
+				vec![
+					#(
+						crate::metadata::CallMetadata {
+							name: stringify!(#fn_name),
+							index: #call_index,
+							args: vec![
+								#(
+									crate::metadata::FieldMetadata {
+										name: stringify!(#args_name),
+										ty: #args_type_name,
+									},
+								)*
+							],
+							docs: vec![ #( #docs ),* ],
+						},
+					)*
+				]
+			}
 		}
 
 		// Dispatch logic at the pallet level, mapping each of the items in the `Call` enum to the
-		// appropriate function call with all arguments, including the `caller`.
-		impl<T: Config> crate::support::Dispatch for #pallet_struct<T> {
-			type Caller = T::AccountId;
-			type Call = Call<T>;
+		// appropriate function call with all arguments, including the `origin`.
+		//
+		// Functions may return either a `DispatchResult` or a `DispatchResultWithPostInfo`.
+		impl #impl_generics crate::support::Dispatch for #pallet_struct #ty_generics
+		where
+			#( #where_predicates, )*
+		{
+			type Origin = T::RuntimeOrigin;
+			type Call = Call #ty_generics;
 
-			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> crate::support::DispatchResult {
+			fn dispatch(
+				&mut self,
+				origin: Self::Origin,
+				call: Self::Call,
+			) -> crate::support::DispatchResultWithPostInfo {
 				match call {
 					#(
 						Call::#fn_name { #( #args_name ),* } => {
 							self.#fn_name(
-								// Note that we assume the first argument of every call is the `caller`.
-								caller,
+								// Note that we assume the first argument of every call is the `origin`.
+								origin,
 								#( #args_name ),*
-							)?;
+							)
+							.map(Into::into)
+							.map_err(Into::into)
 						},
 					)*
+					Call::__Ignore(_, never) => match never {},
 				}
-				Ok(())
 			}
 		}
 	};
 
 	// Return the generated code.
-	dispatch_impl.into()
+	dispatch_impl
 }
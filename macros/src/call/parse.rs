@@ -4,7 +4,7 @@ use syn::spanned::Spanned;
 // Custom keywords we match to when parsing the calls in a pallet.
 mod keyword {
 	syn::custom_keyword!(T);
-	syn::custom_keyword!(AccountId);
+	syn::custom_keyword!(RuntimeOrigin);
 }
 
 /// This object will collect all the information we need to keep while parsing the callable
@@ -14,6 +14,9 @@ pub struct CallDef {
 	/// This is the name of the pallet struct where the callable functions are implemented. We
 	/// mostly assume it is `Pallet`.
 	pub pallet_struct: syn::Ident,
+	/// The generics of the `impl` block, usually `<T: Config>`, or `<T: Config<I>, I: Instance>`
+	/// for an instantiable pallet. The pallet struct and the `Call` enum take the same parameters.
+	pub generics: syn::Generics,
 	/// This is a list of the callable functions exposed by this pallet. See `CallVariantDef`.
 	pub methods: Vec<CallVariantDef>,
 }
@@ -25,10 +28,23 @@ pub struct CallVariantDef {
 	pub name: syn::Ident,
 	/// Information on args of the function: `(name, type)`.
 	pub args: Vec<(syn::Ident, Box<syn::Type>)>,
+	/// The expression given in the `#[weight(..)]` attribute of the function.
+	pub weight: syn::Expr,
+	/// The index given in the `#[call_index(..)]` attribute of the function, which identifies the
+	/// call in its encoding.
+	pub index: u8,
+	/// Whether the function has an `#[allow_unsigned]` attribute, which lets unsigned extrinsics
+	/// make the call.
+	pub allow_unsigned: bool,
+	/// The doc comments of the function, for the metadata.
+	pub docs: Vec<String>,
 }
 
 impl CallDef {
-	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+	/// Parse the callable functions of `item`. The `#[weight(..)]`, `#[call_index(..)]` and
+	/// `#[allow_unsigned]` attributes are removed from the functions, since they are not real
+	/// attributes.
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
 		// First we check that we are parsing an `impl`.
 		let item_impl = if let syn::Item::Impl(item) = item {
 			item
@@ -45,7 +61,7 @@ impl CallDef {
 
 		// Here is where we will store all the callable functions.
 		let mut methods = vec![];
-		for item in item_impl.items {
+		for item in item_impl.items.iter_mut() {
 			if let syn::ImplItem::Fn(method) = item {
 				// Here is where we will store all the args for each callable functions.
 				let mut args = vec![];
@@ -59,22 +75,22 @@ impl CallDef {
 					},
 				}
 
-				// The second argument should be the `caller: T::AccountId` argument.
-				match method.sig.inputs.iter().skip(1).next() {
+				// The second argument should be the `origin: T::RuntimeOrigin` argument.
+				match method.sig.inputs.iter().nth(1) {
 					Some(syn::FnArg::Typed(arg)) => {
 						// Here we specifically check that this argument is as we expect for
-						// `caller: T::AccountId`.
-						check_caller_arg(arg)?;
+						// `origin: T::RuntimeOrigin`.
+						check_origin_arg(arg)?;
 					},
 					_ => {
-						let msg = "Invalid call, second argument should be `caller: T::AccountId`";
+						let msg = "Invalid call, second argument should be `origin: T::RuntimeOrigin`";
 						return Err(syn::Error::new(method.sig.span(), msg))
 					},
 				}
 
 				let fn_name = method.sig.ident.clone();
 
-				// Parsing the rest of the args. Skipping 2 for `self` and `caller`.
+				// Parsing the rest of the args. Skipping 2 for `self` and `origin`.
 				for arg in method.sig.inputs.iter().skip(2) {
 					// All arguments should be typed.
 					let arg = if let syn::FnArg::Typed(arg) = arg {
@@ -95,43 +111,84 @@ impl CallDef {
 					args.push((arg_ident, arg.ty.clone()));
 				}
 
+				// Every callable function must declare its weight.
+				let weight_attr = method.attrs.iter().position(|attr| attr.path().is_ident("weight"));
+				let weight = match weight_attr {
+					Some(i) => method.attrs.remove(i).parse_args::<syn::Expr>()?,
+					None => {
+						let msg = "Invalid call, missing `#[weight(..)]` attribute";
+						return Err(syn::Error::new(method.sig.span(), msg))
+					},
+				};
+
+				// Every callable function must declare its index, which must be unique, so that
+				// reordering the functions does not change the meaning of encoded calls.
+				let index_attr = method.attrs.iter().position(|attr| attr.path().is_ident("call_index"));
+				let index = match index_attr {
+					Some(i) => {
+						let attr = method.attrs.remove(i);
+						let index = attr.parse_args::<syn::LitInt>()?.base10_parse::<u8>()?;
+						if methods.iter().any(|method: &CallVariantDef| method.index == index) {
+							let msg = "Invalid call, duplicate call index";
+							return Err(syn::Error::new(attr.span(), msg))
+						}
+						index
+					},
+					None => {
+						let msg = "Invalid call, missing `#[call_index(..)]` attribute";
+						return Err(syn::Error::new(method.sig.span(), msg))
+					},
+				};
+
+				// A callable function may opt in to being called by unsigned extrinsics.
+				let unsigned_attr = method.attrs.iter().position(|attr| attr.path().is_ident("allow_unsigned"));
+				let allow_unsigned = match unsigned_attr {
+					Some(i) => {
+						method.attrs.remove(i).meta.require_path_only()?;
+						true
+					},
+					None => false,
+				};
+
+				let docs = crate::metadata::docs(&method.attrs);
+
 				// Store all the function name and the arg data for the function.
-				methods.push(CallVariantDef { name: fn_name, args });
+				methods.push(CallVariantDef { name: fn_name, args, weight, index, allow_unsigned, docs });
 			}
 		}
 
 		// Return all callable functions for this pallet.
-		Ok(Self { pallet_struct, methods })
+		Ok(Self { pallet_struct, generics: item_impl.generics.clone(), methods })
 	}
 }
 
-/// Check caller arg is exactly: `caller: T::AccountId`.
+/// Check origin arg is exactly: `origin: T::RuntimeOrigin`.
 ///
 /// This is kept strict to keep the code simple.
-pub fn check_caller_arg(arg: &syn::PatType) -> syn::Result<()> {
+pub fn check_origin_arg(arg: &syn::PatType) -> syn::Result<()> {
 	pub struct CheckDispatchableFirstArg;
 	impl syn::parse::Parse for CheckDispatchableFirstArg {
 		fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
 			input.parse::<keyword::T>()?;
 			input.parse::<syn::Token![::]>()?;
-			input.parse::<keyword::AccountId>()?;
+			input.parse::<keyword::RuntimeOrigin>()?;
 			Ok(Self)
 		}
 	}
 
-	// This checks the arg name is `caller` or `_caller`.
+	// This checks the arg name is `origin` or `_origin`.
 	if let syn::Pat::Ident(ident) = &*arg.pat {
-		// We also support the name as `_caller` for when the variable is unused.
-		if &ident.ident != "caller" && &ident.ident != "_caller" {
-			let msg = "Invalid name for second parameter: expected `caller: T::AccountId`";
+		// We also support the name as `_origin` for when the variable is unused.
+		if &ident.ident != "origin" && &ident.ident != "_origin" {
+			let msg = "Invalid name for second parameter: expected `origin: T::RuntimeOrigin`";
 			return Err(syn::Error::new(ident.span(), msg))
 		}
 	}
 
-	// This checks the type is `T::AccountId` with `CheckDispatchableFirstArg`
+	// This checks the type is `T::RuntimeOrigin` with `CheckDispatchableFirstArg`
 	let ty = &arg.ty;
 	syn::parse2::<CheckDispatchableFirstArg>(ty.to_token_stream()).map_err(|e| {
-		let msg = "Invalid type for second parameter: expected `caller: T::AccountId`";
+		let msg = "Invalid type for second parameter: expected `origin: T::RuntimeOrigin`";
 		let mut err = syn::Error::new(ty.span(), msg);
 		err.combine(e);
 		err
@@ -0,0 +1,62 @@
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+
+/// See the `fn clone_no_bound` docs at the `lib.rs` of this crate for a high level definition.
+pub fn clone_no_bound(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let input = syn::parse_macro_input!(input as syn::DeriveInput);
+	let name = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	// This is how each value is cloned: by matching its fields, and building the same value out of
+	// their clones.
+	let body = match &input.data {
+		syn::Data::Struct(data) => {
+			let (pattern, clone) = clone_fields(&data.fields);
+			quote! {
+				let Self #pattern = self;
+				Self #clone
+			}
+		},
+		syn::Data::Enum(data) => {
+			let arms = data.variants.iter().map(|variant| {
+				let variant_name = &variant.ident;
+				let (pattern, clone) = clone_fields(&variant.fields);
+				quote! { Self::#variant_name #pattern => Self::#variant_name #clone }
+			});
+			quote! {
+				match self {
+					#( #arms, )*
+				}
+			}
+		},
+		syn::Data::Union(data) => {
+			let msg = "Invalid CloneNoBound, expected struct or enum";
+			return syn::Error::new(data.union_token.span(), msg).to_compile_error().into()
+		},
+	};
+
+	quote! {
+		impl #impl_generics Clone for #name #ty_generics #where_clause {
+			fn clone(&self) -> Self {
+				#body
+			}
+		}
+	}
+	.into()
+}
+
+/// The pattern which binds every field of `fields` by reference, and the expression which builds
+/// the same fields out of their clones.
+fn clone_fields(fields: &syn::Fields) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+	match fields {
+		syn::Fields::Named(fields) => {
+			let names = fields.named.iter().map(|field| &field.ident).collect::<Vec<_>>();
+			(quote! { { #( #names ),* } }, quote! { { #( #names: Clone::clone(#names) ),* } })
+		},
+		syn::Fields::Unnamed(fields) => {
+			let names = (0..fields.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect::<Vec<_>>();
+			(quote! { ( #( #names ),* ) }, quote! { ( #( Clone::clone(#names) ),* ) })
+		},
+		syn::Fields::Unit => (quote! {}, quote! {}),
+	}
+}
@@ -0,0 +1,61 @@
+use super::parse::ErrorDef;
+use quote::quote;
+
+/// See the `fn error` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_error(def: ErrorDef) -> proc_macro2::TokenStream {
+	let ErrorDef { error_enum, variants, docs } = def;
+
+	// This is the index of each variant in the enum, which is also the index reported in the
+	// `ModuleError`.
+	let indices = (0..variants.len()).map(|i| i as u8).collect::<Vec<_>>();
+
+	// This quote block converts the error enum into a `DispatchError::Module`, and describes it
+	// for the metadata of the runtime.
+	//
+	// We match on `*self`, so that a pallet whose calls never fail can declare an empty enum.
+	let error_impl = quote! {
+		impl #error_enum {
+			// The name of the error variant.
+			pub fn as_str(&self) -> &'static str {
+				match *self {
+					#( #error_enum::#variants => stringify!(#variants), )*
+				}
+			}
+
+			// The index of the error variant.
+			pub fn index(&self) -> u8 {
+				match *self {
+					#( #error_enum::#variants => #indices, )*
+				}
+			}
+
+			// Describe every error variant.
+			pub fn metadata() -> Vec<crate::metadata::ErrorMetadata> {
+				vec![
+					#(
+						crate::metadata::ErrorMetadata {
+							name: stringify!(#variants),
+							index: #indices,
+							docs: vec![ #( #docs ),* ],
+						},
+					)*
+				]
+			}
+		}
+
+		// Note that the pallet index is unknown here, so it is left as zero. The runtime fills it
+		// in when routing a call to the pallet.
+		impl From<#error_enum> for crate::support::DispatchError {
+			fn from(error: #error_enum) -> Self {
+				crate::support::DispatchError::Module(crate::support::ModuleError {
+					index: 0,
+					error: error.index(),
+					message: error.as_str(),
+				})
+			}
+		}
+	};
+
+	// Return the generated code.
+	error_impl
+}
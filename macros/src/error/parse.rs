@@ -0,0 +1,47 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Error` enum.
+#[derive(Debug)]
+pub struct ErrorDef {
+	/// This is the name of the error enum. We mostly assume it is `Error`.
+	pub error_enum: syn::Ident,
+	/// This is the list of the variants of the error enum.
+	pub variants: Vec<syn::Ident>,
+	/// The doc comments of each variant, for the metadata.
+	pub docs: Vec<Vec<String>>,
+}
+
+impl ErrorDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::error, expected item enum"))
+		};
+
+		// We keep the error enum simple, so it can be converted into a `DispatchError` by casting
+		// the variant into its index.
+		if !item_enum.generics.params.is_empty() {
+			let msg = "Invalid pallet::error, expected enum without generics";
+			return Err(syn::Error::new(item_enum.generics.span(), msg))
+		}
+
+		let mut variants = vec![];
+		let mut docs = vec![];
+		for variant in item_enum.variants {
+			if !matches!(variant.fields, syn::Fields::Unit) {
+				let msg = "Invalid pallet::error, expected variant without fields";
+				return Err(syn::Error::new(variant.fields.span(), msg))
+			}
+			if let Some((_, discriminant)) = &variant.discriminant {
+				let msg = "Invalid pallet::error, expected variant without explicit discriminant";
+				return Err(syn::Error::new(discriminant.span(), msg))
+			}
+			docs.push(crate::metadata::docs(&variant.attrs));
+			variants.push(variant.ident);
+		}
+
+		Ok(Self { error_enum: item_enum.ident, variants, docs })
+	}
+}
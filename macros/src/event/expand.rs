@@ -0,0 +1,71 @@
+use super::parse::EventDef;
+use quote::quote;
+
+/// See the `fn event` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_event(def: EventDef) -> proc_macro2::TokenStream {
+	let EventDef { event_enum, generics, variants, generate_deposit } = def;
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// This is the name of each variant, with the names and type names of its fields, and its doc
+	// comments.
+	let variant_name = variants.iter().map(|variant| &variant.name).collect::<Vec<_>>();
+	let field_name = variants
+		.iter()
+		.map(|variant| variant.fields.iter().map(|(name, _)| name).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let field_type_name = variants
+		.iter()
+		.map(|variant| variant.fields.iter().map(|(_, type_name)| type_name).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let docs = variants.iter().map(|variant| &variant.docs).collect::<Vec<_>>();
+
+	// This quote block describes the event enum for the metadata of the runtime.
+	let event_impl = quote! {
+		impl #impl_generics #event_enum #ty_generics #where_clause {
+			// Describe every event variant.
+			pub fn metadata() -> Vec<crate::metadata::EventMetadata> {
+				vec![
+					#(
+						crate::metadata::EventMetadata {
+							name: stringify!(#variant_name),
+							fields: vec![
+								#(
+									crate::metadata::FieldMetadata {
+										name: stringify!(#field_name),
+										ty: #field_type_name,
+									},
+								)*
+							],
+							docs: vec![ #( #docs ),* ],
+						},
+					)*
+				]
+			}
+		}
+	};
+
+	// This quote block lets the pallet deposit its events into the storage, from where the runtime
+	// takes them. The `Pallet` struct has the same generics as the event enum, and a handle to the
+	// storage in its `storage` field.
+	let deposit_impl = generate_deposit.then(|| {
+		quote! {
+			impl #impl_generics Pallet #ty_generics #where_clause {
+				// Deposit an event, which is discarded if the call depositing it fails.
+				fn deposit_event(&mut self, event: #event_enum #ty_generics) {
+					self.storage.deposit_event(event);
+				}
+
+				/// Drain the events emitted by this pallet since the last call.
+				pub fn take_events(&mut self) -> Vec<#event_enum #ty_generics> {
+					self.storage.take_events()
+				}
+			}
+		}
+	});
+
+	// Return the generated code.
+	quote! {
+		#event_impl
+		#deposit_impl
+	}
+}
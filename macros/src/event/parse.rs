@@ -0,0 +1,73 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the `Event` enum.
+#[derive(Debug)]
+pub struct EventDef {
+	/// This is the name of the event enum. We mostly assume it is `Event`.
+	pub event_enum: syn::Ident,
+	/// The generics of the event enum, which is usually generic over the `Config` of the pallet.
+	pub generics: syn::Generics,
+	/// This is the list of the variants of the event enum. See `EventVariantDef`.
+	pub variants: Vec<EventVariantDef>,
+	/// Whether to generate `deposit_event` and `take_events` on the `Pallet` struct, as asked with
+	/// `#[macros::event(generate_deposit)]`.
+	pub generate_deposit: bool,
+}
+
+/// This is the metadata we keep about each variant of the event enum.
+#[derive(Debug)]
+pub struct EventVariantDef {
+	/// The variant name.
+	pub name: syn::Ident,
+	/// Information on the fields of the variant: `(name, type name)`.
+	pub fields: Vec<(syn::Ident, String)>,
+	/// The doc comments of the variant.
+	pub docs: Vec<String>,
+}
+
+/// Parse the arguments of the attribute, which are either empty or `generate_deposit`.
+pub fn parse_generate_deposit(attr: proc_macro2::TokenStream) -> syn::Result<bool> {
+	if attr.is_empty() {
+		return Ok(false)
+	}
+	let ident = syn::parse2::<syn::Ident>(attr)?;
+	if ident != "generate_deposit" {
+		return Err(syn::Error::new(ident.span(), "Invalid pallet::event, expected `generate_deposit`"))
+	}
+	Ok(true)
+}
+
+impl EventDef {
+	pub fn try_from(item: syn::Item, generate_deposit: bool) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::event, expected item enum"))
+		};
+
+		let mut variants = vec![];
+		for variant in item_enum.variants {
+			// Fields are described by their name, so tuple variants are not supported.
+			let fields = match &variant.fields {
+				syn::Fields::Named(fields) => fields
+					.named
+					.iter()
+					.map(|field| {
+						let name = field.ident.clone().expect("fields are named");
+						(name, crate::metadata::type_name(&field.ty))
+					})
+					.collect(),
+				syn::Fields::Unit => vec![],
+				syn::Fields::Unnamed(fields) => {
+					let msg = "Invalid pallet::event, expected variant with named fields or without fields";
+					return Err(syn::Error::new(fields.span(), msg))
+				},
+			};
+			let docs = crate::metadata::docs(&variant.attrs);
+			variants.push(EventVariantDef { name: variant.ident, fields, docs });
+		}
+
+		Ok(Self { event_enum: item_enum.ident, generics: item_enum.generics, variants, generate_deposit })
+	}
+}
@@ -1,6 +1,34 @@
 mod call;
+mod clone_no_bound;
+mod error;
+mod event;
+mod metadata;
 mod runtime;
+mod storage;
 
+/// Expand the callable functions of a pallet.
+///
+/// Every function in the `impl` block must take `&mut self` and `origin: T::RuntimeOrigin` as its
+/// first two arguments, and declare its weight with a `#[weight(..)]` attribute. The weight is an
+/// expression of type `support::Weight`, which can refer to the other arguments of the function
+/// by name, as references. Functions return either a `support::DispatchResult`, or a
+/// `support::DispatchResultWithPostInfo` to report that they used less than their declared weight.
+/// Every function must also declare a unique index with a `#[call_index(..)]` attribute, which
+/// identifies it in encoded calls, so that reordering the functions does not change their meaning.
+/// A function which unsigned extrinsics may call, and so pay no fee for, says so with an
+/// `#[allow_unsigned]` attribute. The `impl` block may be empty. An instantiable pallet implements its calls for
+/// `impl<T: Config<I>, I: support::Instance> Pallet<T, I>`. This generates:
+/// - `enum Call<T>` - an enum with one variant for each function, holding its other arguments,
+///   and a hidden variant which cannot be built. It takes the same parameters as the `impl` block,
+///   with the instance `I` defaulting to `()`.
+/// - implements `codec::Encode` and `codec::Decode` for `Call<T>`, as the call index of the
+///   function followed by its arguments.
+/// - implements the trait `support::GetDispatchInfo` for `Call<T>`, which evaluates the weight
+///   expression of the function, and tells whether it has an `#[allow_unsigned]` attribute.
+/// - implements the trait `support::Dispatch` for the pallet, to route a `Call<T>` to the
+///   appropriate function.
+/// - `fn metadata()` on `Call<T>` - which describes every function: its name, index, arguments
+///   and doc comments.
 #[proc_macro_attribute]
 pub fn call(
 	attr: proc_macro::TokenStream,
@@ -9,22 +37,137 @@ pub fn call(
 	call::call(attr, item)
 }
 
+/// Expand the `Error` enum of a pallet.
+///
+/// The enum must not be generic, and its variants must not have fields. This generates:
+/// - `fn as_str()` and `fn index()` - which return the name and the index of an error variant.
+/// - `impl From<Error> for support::DispatchError` - which converts an error into a
+///   `DispatchError::Module`. The pallet index is left as zero, and filled in by the runtime when
+///   it dispatches a call to the pallet, unless a pallet which returns the error to another pallet
+///   already tagged it with its index, as told by `support::PalletInfo`.
+/// - `fn metadata()` - which describes every error variant: its name, index and doc comments.
+#[proc_macro_attribute]
+pub fn error(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	error::error(attr, item)
+}
+
+/// Expand the `Event` enum of a pallet.
+///
+/// The variants of the enum must either have named fields or no fields. This generates:
+/// - `fn metadata()` - which describes every event variant: its name, fields and doc comments.
+///
+/// With `#[macros::event(generate_deposit)]`, this also generates on the `Pallet` struct, which
+/// must have the same generics as the enum, and a `storage: storage::Storage` field:
+/// - `fn deposit_event(event)` - which deposits an event into the storage. Like the changes to the
+///   storage, it is discarded if the storage transaction it was deposited in is rolled back.
+/// - `fn take_events()` - which drains the events of the pallet, for the runtime to collect them.
+#[proc_macro_attribute]
+pub fn event(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	event::event(attr, item)
+}
+
+/// Expand the storage of a pallet.
+///
+/// This goes on the pallet struct, whose fields are its storage items, of the types
+/// `storage::StorageValue`, `storage::StorageMap` and `storage::StorageDoubleMap`. Each item is
+/// stored under the `PALLET` constant of the module and the name of its field in `UpperCamelCase`.
+/// If the struct has an instance type parameter `I: support::Instance`, the `PALLET` constant is
+/// prefixed with `I::PREFIX`.
+/// An item is `Default::default()` when it is empty, unless its field has a `#[default(..)]`
+/// attribute with another expression. This generates:
+/// - `fn new(storage)` - which creates the pallet, with every storage item reading and writing
+///   through `storage`. A field of type `storage::Storage` gets the handle itself, and any other
+///   field starts with its default value.
+/// - `impl storage::FromStorage` - which creates the pallet the same way, so that the runtime can
+///   wire a handle to it into the pallets which use it.
+#[proc_macro_attribute]
+pub fn storage(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	storage::storage(attr, item)
+}
+
 /// Expand the `Runtime` definition.
 ///
+/// Every field after `system` holds a pallet, whose type is written as `module::Pallet<Self>`, or
+/// `module::Pallet<Self, Instance>` for an instance of an instantiable pallet. The `Call`, `Event`,
+/// `Error` and `GenesisConfig` of the pallet are found in `module`, and take the same type
+/// arguments. The name of the field names the pallet, so the same module can be included more than
+/// once under different names. Every pallet must declare a unique index with a `#[pallet_index(..)]`
+/// attribute, which identifies it in encoded calls and in its errors. Index 0 is reserved for the
+/// system pallet, which must not declare one. A pallet whose `Config` names other pallets of the
+/// runtime, like the currency it reserves deposits in, lists their fields in a `#[uses(..)]`
+/// attribute, and must implement `support::UsesPallet` for each of them: before dispatching each
+/// of its calls, the runtime wires into it a new handle to each of those pallets, created with
+/// `storage::FromStorage` on the storage of the runtime.
+///
 /// This generates function implementations on `Runtime`:
-/// - `fn new()` - which generates a new instance of the runtime, by instantiating all the pallets
-///   included in the runtime.
+/// - `fn new(storage)` - which generates a new instance of the runtime, by instantiating all the
+///   pallets included in the runtime with a handle to the same `storage::Storage`.
+/// - `fn build_genesis()` - which builds the genesis state of every pallet from a
+///   `RuntimeGenesisConfig`, and stores the hash of the genesis block. Every pallet must define a
+///   `GenesisConfig<T>` struct, and a `build_genesis()` function which returns a
+///   `support::GenesisError` if the configuration is invalid.
+/// - `fn state_root()` - which hashes every key-value pair in the storage.
+/// - `fn initialize_block()`, `fn apply_extrinsic()` and `fn finalize_block()` - the steps of
+///   executing a block, which are also used to build new blocks one extrinsic at a time. Every
+///   pallet must implement `support::Hooks`: `initialize_block` calls the `on_initialize` hook of
+///   every pallet, and `finalize_block` their `on_idle` and then their `on_finalize` hooks, in the
+///   order the pallets are declared. Events emitted by hooks are recorded with the
+///   `system::Phase` they were emitted in.
 /// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics. It does
-///   basic actions like incrementing the block number and checking the block to be executed has a
-///   valid block number.
+///   basic actions like incrementing the block number and checking the block number, parent hash,
+///   extrinsics root and state root in the header, and the signature, checkpoint and nonce of
+///   every signed extrinsic. The total weight of the calls in a block must not exceed
+///   `system::Config::BLOCK_WEIGHT_LIMIT`, and the caller of every signed extrinsic must pay its
+///   fee, through the `support::ChargeTransaction` trait which the runtime must implement. The fee
+///   for weight a call did not use is refunded after dispatching it. Unsigned extrinsics are free,
+///   and dispatched with `Origin::None`, so they may only make the calls with an
+///   `#[allow_unsigned]` attribute. An invalid block is rejected with a `support::BlockError`,
+///   and none of its changes are kept. Every extrinsic is dispatched as a storage transaction,
+///   which is rolled back if the call returns an error. Once a block is imported, its changes are
+///   flushed to the storage backend. Events from the previous block are cleared, and an
+///   `ExtrinsicSuccess` or `ExtrinsicFailed` system event is deposited for every extrinsic. In
+///   debug builds, the `try_state` hook of every pallet then checks its invariants, as it does
+///   after building the genesis state.
 ///
 /// This also generates code needed for dispatching calls to the pallets:
 /// - Note: For simplicity, we assume that the system pallet is not callable.
 /// - `enum RuntimeCall` - an "outer"-enum representing the accumulation of all possible calls to
 ///   all pallets. The system pallet is not included.
-/// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. Basic
-///   logic like incrementing the nonce of the user is included in the generated code. The system
-///   pallet is not included.
+/// - implements `codec::Encode` and `codec::Decode` for `RuntimeCall`, as the pallet index
+///   followed by the encoded pallet level call. Calls can hold other calls, but no deeper than
+///   `support::MAX_CALL_DEPTH`.
+/// - implements the trait `support::GetDispatchInfo` for `RuntimeCall`, which returns the dispatch
+///   info of the pallet level call.
+/// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. The
+///   system pallet is not included. Every pallet must implement `support::DispatchNested`: once
+///   the call of a pallet succeeds, the calls it hands over, like those made through `sudo`, are
+///   dispatched in turn, and their result is reported back to the pallet. The calls handed over by
+///   a call which fails are discarded.
+/// - implements the trait `support::PalletInfo`, which tells the index of every pallet by its type.
+///
+/// And `struct RuntimeGenesisConfig`, which holds the `GenesisConfig` of every pallet, keyed by the
+/// name of the pallet in the runtime. It can be deserialized from a chain spec.
+///
+/// And the code needed for collecting events:
+/// - `enum RuntimeEvent` - an "outer"-enum representing the accumulation of all possible events
+///   emitted by all pallets, including system. Every pallet must define an `Event<T>` enum, and a
+///   `take_events()` function which drains the events it emitted, as generated by
+///   `#[macros::event(generate_deposit)]`.
+/// - `fn collect_events()` - which moves the events emitted by every pallet into the event log of
+///   the system pallet, tagged with the index of the extrinsic which emitted them.
+///
+/// And `fn metadata()`, which describes every pallet: its name, its index, and the calls, events
+/// and errors described by its `#[macros::call]`, `#[macros::event]` and `#[macros::error]`.
+/// Every pallet must therefore define an `Error` enum, even if it is empty.
 #[proc_macro_attribute]
 pub fn runtime(
 	attr: proc_macro::TokenStream,
@@ -32,3 +175,13 @@ pub fn runtime(
 ) -> proc_macro::TokenStream {
 	runtime::runtime(attr, item)
 }
+
+/// Derive `Clone` for a struct or enum which is generic over the `Config` of a pallet, like its
+/// `Event` or `GenesisConfig`.
+///
+/// `#[derive(Clone)]` would require the type parameters themselves to be `Clone`, which a `Config`
+/// like the `Runtime` has no reason to be. This instead clones every field, without any bound.
+#[proc_macro_derive(CloneNoBound)]
+pub fn clone_no_bound(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	clone_no_bound::clone_no_bound(input)
+}
@@ -0,0 +1,33 @@
+use quote::ToTokens;
+
+/// The doc comments in `attrs`, one line per item, without the space which follows `///`.
+pub fn docs(attrs: &[syn::Attribute]) -> Vec<String> {
+	attrs
+		.iter()
+		.filter(|attr| attr.path().is_ident("doc"))
+		.filter_map(|attr| match &attr.meta {
+			syn::Meta::NameValue(syn::MetaNameValue {
+				value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }),
+				..
+			}) => {
+				let doc = doc.value();
+				Some(doc.strip_prefix(' ').unwrap_or(&doc).to_string())
+			},
+			_ => None,
+		})
+		.collect()
+}
+
+/// The name of `ty` as it is written in the source, like `T::AccountId` or `Vec<u8>`.
+pub fn type_name(ty: &syn::Type) -> String {
+	// Tokens are printed with spaces between them, which we remove where nobody would write them.
+	ty.to_token_stream()
+		.to_string()
+		.replace(" :: ", "::")
+		.replace(" < ", "<")
+		.replace("< ", "<")
+		.replace(" <", "<")
+		.replace(" >", ">")
+		.replace(" ,", ",")
+		.replace("& ", "&")
+}
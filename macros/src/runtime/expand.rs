@@ -6,41 +6,353 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 	let RuntimeDef { runtime_struct, pallets } = def;
 
 	// This is a vector of all the pallet names, not including system.
-	let pallet_names = pallets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+	let pallet_names = pallets.iter().map(|pallet| pallet.name.clone()).collect::<Vec<_>>();
 	// This is a vector of all the pallet types, not including system.
-	let pallet_types = pallets.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>();
+	let pallet_types = pallets.iter().map(|pallet| pallet.ty.clone()).collect::<Vec<_>>();
+	// This is a vector of the module of each pallet, and of its type arguments, like `balances`
+	// and `<Runtime, Instance1>`. Instances of the same pallet share a module, but not their name.
+	let pallet_modules = pallets.iter().map(|pallet| pallet.module.clone()).collect::<Vec<_>>();
+	let pallet_args = pallets
+		.iter()
+		.map(|pallet| {
+			let args = &pallet.args;
+			quote! { <#( #args ),*> }
+		})
+		.collect::<Vec<_>>();
+	// This is a vector of the index of each pallet, as declared by its `#[pallet_index(..)]`
+	// attribute. System is always at index 0.
+	let pallet_indices = pallets.iter().map(|pallet| pallet.index).collect::<Vec<_>>();
+	// For each pallet, the code which wires a handle to each pallet of its `#[uses(..)]` attribute
+	// into it before dispatching its call. The handles are created on the storage of the runtime, so
+	// they read and write the same state as the pallets of the runtime.
+	let pallet_wirings = pallets
+		.iter()
+		.map(|pallet| {
+			let name = &pallet.name;
+			let used_types = pallet
+				.uses
+				.iter()
+				.map(|used| &pallets.iter().find(|other| other.name == *used).expect("checked when parsing").ty);
+			quote! {
+				#(
+					crate::support::UsesPallet::<#used_types>::wire(
+						&mut self.#name,
+						<#used_types as crate::storage::FromStorage>::from_storage(storage.clone()),
+					);
+				)*
+			}
+		})
+		.collect::<Vec<_>>();
 
 	// This quote block implements functions on the `Runtime` struct.
 	let runtime_impl = quote! {
 		impl #runtime_struct {
 			// Create a new instance of the main Runtime, by creating a new instance of each pallet.
-			fn new() -> Self {
+			// Every pallet reads and writes through the same `storage`.
+			fn new(storage: crate::storage::Storage) -> Self {
 				Self {
 					// Since system is not included in the list of pallets, we manually add it here.
-					system: <system::Pallet::<Self>>::new(),
+					system: <system::Pallet::<Self>>::new(storage.clone()),
 					#(
-						#pallet_names: <#pallet_types>::new()
+						#pallet_names: <#pallet_types>::new(storage.clone())
 					),*
 				}
 			}
 
+			// Build the genesis state of every pallet out of `config`, and store the hash of the
+			// genesis block. None of the state is kept if any pallet rejects its configuration.
+			fn build_genesis(
+				&mut self,
+				config: &RuntimeGenesisConfig,
+			) -> Result<(), crate::support::GenesisError> {
+				let storage = self.system.storage().clone();
+				storage.with_transaction(|| {
+					self.system.build_genesis(&config.system)?;
+					#( self.#pallet_names.build_genesis(&config.#pallet_names)?; )*
+					// The genesis block has no parent and no extrinsics.
+					let header = self.build_header(crate::support::Hash::default(), &[]);
+					self.system.set_genesis_hash(crate::support::blake2_256(&crate::codec::Encode::encode(
+						&header,
+					)));
+					Ok(())
+				})?;
+				storage.flush().map_err(|error| crate::support::GenesisError::Storage(error.kind()))?;
+				if cfg!(debug_assertions) {
+					self.try_state();
+				}
+				Ok(())
+			}
+
+			// Check the invariants of the state of every pallet with its `try_state` hook, and
+			// panic if any of them does not hold.
+			fn try_state(&self) {
+				#(
+					if let Err(error) = crate::support::Hooks::try_state(&self.#pallet_names) {
+						panic!("the state of `{}` is corrupt: {}", stringify!(#pallet_names), error);
+					}
+				)*
+			}
+
+			// Compute the state root, by hashing every key-value pair in the storage.
+			fn state_root(&self) -> crate::support::Hash {
+				self.system.storage().root()
+			}
+
+			// The header of the block with the current block number, whose parent is
+			// `parent_hash`, and which contains `extrinsics`, matching the current state.
+			fn build_header(
+				&self,
+				parent_hash: crate::support::Hash,
+				extrinsics: &[types::Extrinsic],
+			) -> types::Header {
+				types::Header {
+					parent_hash,
+					block_number: self.system.block_number(),
+					state_root: self.state_root(),
+					extrinsics_root: crate::support::blake2_256(&crate::codec::Encode::encode(extrinsics)),
+				}
+			}
+
 			// Execute a block of extrinsics. Increments the block number.
-			fn execute_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
-				self.system.inc_block_number();
+			//
+			// If the block is invalid, none of its changes are kept. Changes made by an extrinsic
+			// which fails to dispatch are rolled back, but do not make the block invalid. Once the
+			// block is imported, its changes are committed to the storage backend, and the block is
+			// not imported if they cannot be.
+			fn execute_block(&mut self, block: types::Block) -> Result<(), crate::support::BlockError> {
+				let storage = self.system.storage().clone();
+				if let Err(error) = storage.with_transaction(|| self.import_block(block)) {
+					self.reset_events();
+					return Err(error)
+				}
+				// The changes of the block are discarded if they cannot be written, so its events are
+				// discarded with them.
+				if let Err(error) = storage.flush() {
+					self.reset_events();
+					return Err(crate::support::BlockError::Storage(error.kind()))
+				}
+				if cfg!(debug_assertions) {
+					self.try_state();
+				}
+				Ok(())
+			}
+
+			// Check the header of the block against the current state, execute its extrinsics and
+			// hooks, and check the header against the resulting state. Stores the hash of the block.
+			fn import_block(&mut self, block: types::Block) -> Result<(), crate::support::BlockError> {
+				if self.system.genesis_hash().is_none() {
+					return Err(crate::support::BlockError::NoGenesis)
+				}
+				let parent_hash = self.initialize_block();
 				if block.header.block_number != self.system.block_number() {
-					return Err(&"block number does not match what is expected")
+					return Err(crate::support::BlockError::InvalidBlockNumber)
+				}
+				if block.header.parent_hash != parent_hash {
+					return Err(crate::support::BlockError::InvalidParentHash)
+				}
+				let extrinsics_root =
+					crate::support::blake2_256(&crate::codec::Encode::encode(&block.extrinsics));
+				if block.header.extrinsics_root != extrinsics_root {
+					return Err(crate::support::BlockError::InvalidExtrinsicsRoot)
 				}
-				for (i, support::Extrinsic { caller, call }) in block.extrinsics.into_iter().enumerate() {
-					self.system.inc_nonce(&caller);
-					let _res = self.dispatch(caller, call).map_err(|e| {
-						eprintln!(
-							"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
-							block.header.block_number, i, e
-						)
-					});
+				for (i, extrinsic) in block.extrinsics.into_iter().enumerate() {
+					// A call which fails to dispatch does not make the block invalid.
+					let _ = self.apply_extrinsic(i as u32, extrinsic)?;
 				}
+				self.finalize_block();
+				if block.header.state_root != self.state_root() {
+					return Err(crate::support::BlockError::InvalidStateRoot)
+				}
+				self.system.set_block_hash(
+					block.header.block_number,
+					crate::support::blake2_256(&crate::codec::Encode::encode(&block.header)),
+				);
 				Ok(())
 			}
+
+			// Start the next block: increment the block number, clear the events of the previous
+			// block, and run the `on_initialize` hook of every pallet. Returns the hash of the
+			// parent block.
+			fn initialize_block(&mut self) -> crate::support::Hash {
+				let parent_hash = self
+					.system
+					.block_hash(&self.system.block_number())
+					.expect("the hash of the current block is always known");
+				self.system.inc_block_number();
+				self.system.reset_block_weight();
+				// Events are only kept for the block currently being executed.
+				self.reset_events();
+				let block_number = self.system.block_number();
+				#(
+					let weight =
+						crate::support::Hooks::on_initialize(&mut self.#pallet_names, block_number);
+					self.system.register_extra_weight(weight);
+				)*
+				self.collect_events(system::Phase::Initialization);
+				parent_hash
+			}
+
+			// End the current block, once all its extrinsics are applied: run the `on_idle` hook of
+			// every pallet with the weight left in the block, and then their `on_finalize` hook.
+			fn finalize_block(&mut self) {
+				let block_number = self.system.block_number();
+				#(
+					let remaining_weight = self.system.remaining_weight();
+					let weight =
+						crate::support::Hooks::on_idle(&mut self.#pallet_names, remaining_weight);
+					self.system.register_extra_weight(weight.min(remaining_weight));
+				)*
+				#( crate::support::Hooks::on_finalize(&mut self.#pallet_names, block_number); )*
+				self.collect_events(system::Phase::Finalization);
+			}
+
+			// Apply a single extrinsic, which is at `extrinsic_index` in the current block, and
+			// return the result of dispatching its call.
+			//
+			// Changes made by an extrinsic which fails to dispatch are rolled back, but its caller
+			// still pays its fee. A signed extrinsic with an invalid signature or checkpoint, or
+			// whose nonce does not match the nonce of its caller, cannot be included in a block at
+			// all, and neither can an extrinsic whose weight does not fit in the block, or whose
+			// caller cannot pay its fee, or an unsigned extrinsic whose call does not allow it. Such
+			// an extrinsic makes the whole block invalid, so the changes made while checking it are
+			// left for the caller of this function to roll back.
+			fn apply_extrinsic(
+				&mut self,
+				extrinsic_index: u32,
+				extrinsic: types::Extrinsic,
+			) -> Result<crate::support::DispatchResult, crate::support::BlockError> {
+				// Fees depend on the length of the extrinsic as it is included in the block.
+				let len = crate::codec::Encode::encode(&extrinsic).len() as u32;
+				let support::Extrinsic { signature, call } = extrinsic;
+				let info = crate::support::GetDispatchInfo::get_dispatch_info(&call);
+				// The caller of a signed extrinsic, its tip and the amount it paid for its fee.
+				let mut payment = None;
+				let origin = match signature {
+					Some(crate::support::SignaturePayload { caller, nonce, checkpoint, tip, signature }) => {
+						let Some(genesis_hash) = self.system.genesis_hash() else {
+							return Err(crate::support::BlockError::NoGenesis)
+						};
+						let Some(checkpoint_hash) = self.system.block_hash(&checkpoint) else {
+							return Err(crate::support::BlockError::BadCheckpoint { extrinsic_index })
+						};
+						let payload = crate::support::signing_payload(
+							&call,
+							&nonce,
+							&tip,
+							&genesis_hash,
+							&checkpoint_hash,
+						);
+						if !crate::support::Verify::verify(&signature, &payload, &caller) {
+							return Err(crate::support::BlockError::BadSignature { extrinsic_index })
+						}
+						// The nonce of an account starts over when it is created again, so only the
+						// extrinsics signed since then are valid.
+						if self.system.created(&caller).is_none_or(|created| created > checkpoint) {
+							return Err(crate::support::BlockError::BadCheckpoint { extrinsic_index })
+						}
+						match nonce.cmp(&self.system.nonce(&caller)) {
+							core::cmp::Ordering::Less => {
+								return Err(crate::support::BlockError::StaleNonce { extrinsic_index })
+							},
+							core::cmp::Ordering::Greater => {
+								return Err(crate::support::BlockError::FutureNonce { extrinsic_index })
+							},
+							core::cmp::Ordering::Equal => {},
+						}
+						if !self.system.consume_weight(info.weight) {
+							return Err(crate::support::BlockError::ExhaustsResources { extrinsic_index })
+						}
+						let Some(paid) =
+							crate::support::ChargeTransaction::withdraw_fee(self, &caller, &info, len, tip)
+						else {
+							return Err(crate::support::BlockError::CannotPayFees { extrinsic_index })
+						};
+						self.system.inc_nonce(&caller);
+						payment = Some((caller.clone(), tip, paid));
+						crate::support::Origin::Signed(caller)
+					},
+					None => {
+						// Nobody pays for an unsigned extrinsic, so only the calls which allow it can
+						// be made this way.
+						if !info.allow_unsigned {
+							return Err(crate::support::BlockError::Unsigned { extrinsic_index })
+						}
+						if !self.system.consume_weight(info.weight) {
+							return Err(crate::support::BlockError::ExhaustsResources { extrinsic_index })
+						}
+						crate::support::Origin::None
+					},
+				};
+				// Each extrinsic is dispatched inside its own storage transaction, so that a failed
+				// call leaves no trace other than the nonce bump and fee payment above, and the
+				// `ExtrinsicFailed` event. The events it emitted are discarded with its changes.
+				let storage = self.system.storage().clone();
+				let res = storage.with_transaction(|| self.dispatch(origin.into(), call));
+				let post_info = match &res {
+					Ok(post_info) => *post_info,
+					Err(error) => error.post_info,
+				};
+				// The weight the call did not use is given back to the block, and its fee to the
+				// caller.
+				self.system.refund_weight(info.weight - post_info.calc_actual_weight(&info));
+				if let Some((caller, tip, paid)) = payment {
+					let settlement = crate::support::FeeSettlement { info, post_info, len, tip, paid };
+					crate::support::ChargeTransaction::settle_fee(self, &caller, &settlement);
+				}
+				let phase = system::Phase::ApplyExtrinsic(extrinsic_index);
+				self.collect_events(phase);
+				let event = match &res {
+					Ok(_) => system::Event::ExtrinsicSuccess,
+					Err(error) => system::Event::ExtrinsicFailed { error: error.error },
+				};
+				self.system.deposit_event(phase, RuntimeEvent::system(event));
+				Ok(res.map(|_| ()).map_err(|error| error.error))
+			}
+		}
+	};
+
+	// This quote block implements the `RuntimeGenesisConfig` struct, which holds the genesis
+	// configuration of every pallet.
+	let genesis_impl = quote! {
+		// The genesis configuration of the runtime, as found in a chain spec. Pallets which are
+		// missing from it use their default configuration.
+		#[derive(Debug, Clone, Default, serde::Deserialize)]
+		#[serde(default, deny_unknown_fields)]
+		pub struct RuntimeGenesisConfig {
+			pub system: system::GenesisConfig<#runtime_struct>,
+			#( pub #pallet_names: #pallet_modules::GenesisConfig #pallet_args ),*
+		}
+	};
+
+	// This quote block implements the `RuntimeEvent` enum, and the logic to move the events emitted
+	// by each pallet into the event log of the system pallet.
+	let event_impl = quote! {
+		// These are all the events which can be emitted by the runtime.
+		// Note that it is just an accumulation of the events emitted by each pallet.
+		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone)]
+		pub enum RuntimeEvent {
+			system(system::Event),
+			#( #pallet_names(#pallet_modules::Event #pallet_args) ),*
+		}
+
+		impl #runtime_struct {
+			// Clear the event log of the system pallet, and discard any events emitted by pallets
+			// outside of a block.
+			fn reset_events(&mut self) {
+				self.system.reset_events();
+				#( self.#pallet_names.take_events(); )*
+			}
+
+			// Collect the events emitted by every pallet and deposit them into the system pallet,
+			// recording the phase of the block which emitted them.
+			fn collect_events(&mut self, phase: system::Phase) {
+				#(
+					for event in self.#pallet_names.take_events() {
+						self.system.deposit_event(phase, RuntimeEvent::#pallet_names(event));
+					}
+				)*
+			}
 		}
 	};
 
@@ -51,41 +363,158 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone)]
 		pub enum RuntimeCall {
-			#( #pallet_names(#pallet_names::Call<#runtime_struct>) ),*
+			#( #pallet_names(#pallet_modules::Call #pallet_args) ),*
+		}
+
+		// Encode the index of the pallet followed by the pallet level call.
+		impl crate::codec::Encode for RuntimeCall {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => {
+							dest.push(#pallet_indices);
+							crate::codec::Encode::encode_to(call, dest);
+						}
+					),*
+				}
+			}
+		}
+
+		// Decode the index of the pallet, and then the call of that pallet. Calls can hold other
+		// calls, so their depth is limited.
+		impl crate::codec::Decode for RuntimeCall {
+			fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+				crate::codec::decode_with_depth_limit(input, crate::support::MAX_CALL_DEPTH, |input| {
+					match <u8 as crate::codec::Decode>::decode(input)? {
+						#(
+							#pallet_indices => Ok(RuntimeCall::#pallet_names(
+								crate::codec::Decode::decode(input)?,
+							)),
+						)*
+						_ => Err(crate::codec::Error::InvalidVariant),
+					}
+				})
+			}
+		}
+
+		// The weight of a call is the weight declared by its pallet.
+		impl crate::support::GetDispatchInfo for RuntimeCall {
+			fn get_dispatch_info(&self) -> crate::support::DispatchInfo {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => {
+							crate::support::GetDispatchInfo::get_dispatch_info(call)
+						}
+					),*
+				}
+			}
+		}
+
+		// Pallets are told apart by their type, which includes their instance.
+		impl crate::support::PalletInfo for #runtime_struct {
+			fn index<P: 'static>() -> Option<u8> {
+				let pallet = core::any::TypeId::of::<P>();
+				#(
+					if pallet == core::any::TypeId::of::<#pallet_modules::Pallet #pallet_args>() {
+						return Some(#pallet_indices)
+					}
+				)*
+				None
+			}
 		}
 
 		impl crate::support::Dispatch for #runtime_struct {
-			type Caller = <Runtime as system::Config>::AccountId;
+			type Origin = <#runtime_struct as system::Config>::RuntimeOrigin;
 			type Call = RuntimeCall;
-			// Dispatch a call on behalf of a caller. Increments the caller's nonce.
+			// Dispatch a call on behalf of an origin.
 			//
 			// Dispatch allows us to identify which underlying pallet call we want to execute.
-			// Note that we extract the `caller` from the extrinsic, and use that information
-			// to determine who we are executing the call on behalf of.
+			// Note that the `origin` is built from the `caller` of the extrinsic, and we use
+			// that information to determine who we are executing the call on behalf of.
 			fn dispatch(
 				&mut self,
-				caller: Self::Caller,
+				origin: Self::Origin,
 				runtime_call: Self::Call,
-			) -> crate::support::DispatchResult {
+			) -> crate::support::DispatchResultWithPostInfo {
 				// This match statement will allow us to correctly route `RuntimeCall`s
 				// to the appropriate pallet level call.
 				match runtime_call {
 					#(
 						RuntimeCall::#pallet_names(call) => {
-							self.#pallet_names.dispatch(caller, call)?;
+							let storage = self.system.storage().clone();
+							#pallet_wirings
+							// Errors which pallets did not tag with an index come from the pallet whose
+							// call this is.
+							let result = self.#pallet_names
+								.dispatch(origin, call)
+								.map_err(|e| e.with_pallet_index(#pallet_indices));
+							// Then we dispatch the calls the pallet handed over, if its call succeeded.
+							// Each of them is dispatched in its own storage transaction, which also
+							// discards the events it emitted if it fails. The calls handed over by a
+							// call which failed are discarded, like its changes.
+							while let Some((origin, call)) =
+								crate::support::DispatchNested::<Self::Origin, Self::Call>::take_nested_call(
+									&mut self.#pallet_names,
+								)
+							{
+								if result.is_err() {
+									continue
+								}
+								let nested = storage.with_transaction(|| self.dispatch(origin.clone(), call));
+								crate::support::DispatchNested::<Self::Origin, Self::Call>::on_nested_result(
+									&mut self.#pallet_names,
+									origin,
+									nested,
+								);
+							}
+							result
 						}
 					),*
 				}
-				Ok(())
+			}
+		}
+	};
+
+	// This quote block describes every pallet of the runtime, with the calls, events and errors
+	// described by the pallet level macros.
+	let metadata_impl = quote! {
+		impl #runtime_struct {
+			// The metadata of the runtime, which describes every pallet in the order they are
+			// declared.
+			pub fn metadata() -> crate::metadata::RuntimeMetadata {
+				crate::metadata::RuntimeMetadata {
+					pallets: vec![
+						// The system pallet is not callable, and has no errors of its own.
+						crate::metadata::PalletMetadata {
+							name: "system",
+							index: 0,
+							calls: Vec::new(),
+							events: system::Event::metadata(),
+							errors: Vec::new(),
+						},
+						#(
+							crate::metadata::PalletMetadata {
+								name: stringify!(#pallet_names),
+								index: #pallet_indices,
+								calls: #pallet_modules::Call::#pallet_args::metadata(),
+								events: #pallet_modules::Event::#pallet_args::metadata(),
+								errors: #pallet_modules::Error::metadata(),
+							},
+						)*
+					],
+				}
 			}
 		}
 	};
 
 	// We combine and return all the generated code.
 	quote! {
+		#genesis_impl
+		#event_impl
 		#dispatch_impl
 		#runtime_impl
+		#metadata_impl
 	}
-	.into()
 }
@@ -6,20 +6,20 @@ pub fn runtime(
 	_attr: proc_macro::TokenStream,
 	item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-	// The final expanded code will be placed here.
-	// Since our macro only adds new code, our final product will contain all of our old code too,
-	// hence we clone `item`.
-	let mut finished = item.clone();
-	let item_mod = syn::parse_macro_input!(item as syn::Item);
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
 
-	// First we parse the `Runtime` struct...
-	let generated: proc_macro::TokenStream = match parse::RuntimeDef::try_from(item_mod.clone()) {
+	// First we parse the `Runtime` struct, which also removes the `#[pallet_index(..)]` attributes
+	// from `item_mod`...
+	let generated = match parse::RuntimeDef::try_from(&mut item_mod) {
 		// ..then we generate our new code.
-		Ok(def) => expand::expand_runtime(def).into(),
-		Err(e) => e.to_compile_error().into(),
+		Ok(def) => expand::expand_runtime(def),
+		Err(e) => e.to_compile_error(),
 	};
 
-	// Add our generated code to the end, and return the final result.
-	finished.extend(generated);
-	return finished;
+	// Our final product contains all of our old code, followed by the generated code.
+	quote::quote! {
+		#item_mod
+		#generated
+	}
+	.into()
 }
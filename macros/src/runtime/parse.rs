@@ -6,12 +6,36 @@ pub struct RuntimeDef {
 	/// This is the name of the struct used by the user. We mostly assume it is `Runtime`.
 	pub runtime_struct: syn::Ident,
 	/// This is the list of pallets included in the `Runtime` struct. We omit `system` from this
-	/// list, but during parsing we check that system exists.
-	pub pallets: Vec<(syn::Ident, syn::Type)>,
+	/// list, but during parsing we check that system exists. See `PalletDef`.
+	pub pallets: Vec<PalletDef>,
+}
+
+/// This is what we keep about each pallet included in the `Runtime` struct.
+#[derive(Debug)]
+pub struct PalletDef {
+	/// The name of the field holding the pallet, which is also the name of the pallet in the
+	/// `RuntimeCall`, `RuntimeEvent` and `RuntimeGenesisConfig`.
+	pub name: syn::Ident,
+	/// The type of the pallet, like `balances::Pallet<Self>`.
+	pub ty: syn::Type,
+	/// The module of the pallet, which defines its `Call`, `Event`, `Error` and `GenesisConfig`.
+	/// For example `balances` for `balances::Pallet<Self, Instance1>`.
+	pub module: syn::Path,
+	/// The type arguments of the pallet, with `Self` replaced by the runtime struct. For example
+	/// `Runtime, Instance1` for `balances::Pallet<Self, Instance1>`.
+	pub args: Vec<syn::GenericArgument>,
+	/// The index given in the `#[pallet_index(..)]` attribute of the field, which identifies the
+	/// pallet in encoded calls and in its errors.
+	pub index: u8,
+	/// The names of the pallets given in the `#[uses(..)]` attribute of the field, if any, which are
+	/// wired into the pallet before its calls are dispatched.
+	pub uses: Vec<syn::Ident>,
 }
 
 impl RuntimeDef {
-	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+	/// Parse the `Runtime` struct in `item`. The `#[pallet_index(..)]` and `#[uses(..)]` attributes
+	/// are removed from its fields, since they are not real attributes.
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
 		// First we check that we are parsing a `struct`.
 		let item_struct = if let syn::Item::Struct(item) = item {
 			item
@@ -20,16 +44,60 @@ impl RuntimeDef {
 		};
 
 		// We check that the `Runtime` includes the `system` pallet as the first item.
-		check_system(&item_struct)?;
+		check_system(item_struct)?;
 
-		let runtime_struct = item_struct.ident;
+		let runtime_struct = item_struct.ident.clone();
 
 		// Here is where we will store a list of all the pallets.
 		let mut pallets = vec![];
 		// We skip `system`, which we ensure is the first field in `check_system`.
-		for field in item_struct.fields.into_iter().skip(1) {
-			if let Some(ident) = field.ident {
-				pallets.push((ident, field.ty))
+		for field in item_struct.fields.iter_mut().skip(1) {
+			// Every pallet must declare its index, which must be unique, so that reordering the
+			// fields does not change the meaning of encoded calls. Index 0 is the system pallet.
+			let index_attr = field.attrs.iter().position(|attr| attr.path().is_ident("pallet_index"));
+			let index = match index_attr {
+				Some(i) => {
+					let attr = field.attrs.remove(i);
+					let index = attr.parse_args::<syn::LitInt>()?.base10_parse::<u8>()?;
+					if index == 0 {
+						let msg = "Invalid runtime, pallet index 0 is reserved for system";
+						return Err(syn::Error::new(attr.span(), msg))
+					}
+					if pallets.iter().any(|pallet: &PalletDef| pallet.index == index) {
+						let msg = "Invalid runtime, duplicate pallet index";
+						return Err(syn::Error::new(attr.span(), msg))
+					}
+					index
+				},
+				None => {
+					let msg = "Invalid runtime, missing `#[pallet_index(..)]` attribute";
+					return Err(syn::Error::new(field.span(), msg))
+				},
+			};
+			// A pallet may use other pallets, which are named by their field.
+			let uses = match field.attrs.iter().position(|attr| attr.path().is_ident("uses")) {
+				Some(i) => field
+					.attrs
+					.remove(i)
+					.parse_args_with(
+						syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated,
+					)?
+					.into_iter()
+					.collect(),
+				None => Vec::new(),
+			};
+			if let Some(ident) = field.ident.clone() {
+				pallets.push(PalletDef::try_from(ident, field.ty.clone(), index, uses, &runtime_struct)?)
+			}
+		}
+
+		// Every pallet which is used must be another pallet of the runtime.
+		for pallet in &pallets {
+			for used in &pallet.uses {
+				if *used == pallet.name || !pallets.iter().any(|other| other.name == *used) {
+					let msg = "Invalid runtime, `#[uses(..)]` must name other pallets of the runtime";
+					return Err(syn::Error::new(used.span(), msg))
+				}
 			}
 		}
 
@@ -37,6 +105,45 @@ impl RuntimeDef {
 	}
 }
 
+impl PalletDef {
+	/// Split the type of a pallet, like `balances::Pallet<Self, Instance1>`, into its module and its
+	/// type arguments. The same module can be included more than once with different instances.
+	fn try_from(
+		name: syn::Ident,
+		ty: syn::Type,
+		index: u8,
+		uses: Vec<syn::Ident>,
+		runtime_struct: &syn::Ident,
+	) -> syn::Result<Self> {
+		let msg = "Invalid runtime, expected pallet type like `pallet::Pallet<Self>`";
+		let syn::Type::Path(type_path) = &ty else { return Err(syn::Error::new(ty.span(), msg)) };
+		let mut module = type_path.path.clone();
+		let Some(pair) = module.segments.pop() else { return Err(syn::Error::new(ty.span(), msg)) };
+		// Drop the trailing `::` left behind by the segment we removed.
+		module.segments.pop_punct();
+		if module.segments.is_empty() {
+			return Err(syn::Error::new(ty.span(), msg))
+		}
+
+		let args = match pair.into_value().arguments {
+			syn::PathArguments::AngleBracketed(args) => args
+				.args
+				.into_iter()
+				.map(|arg| match arg {
+					// `Self` means the runtime, but not inside the generated enums.
+					syn::GenericArgument::Type(syn::Type::Path(path)) if path.path.is_ident("Self") => {
+						syn::GenericArgument::Type(syn::parse_quote!(#runtime_struct))
+					},
+					arg => arg,
+				})
+				.collect(),
+			_ => return Err(syn::Error::new(ty.span(), msg)),
+		};
+
+		Ok(Self { name, ty, module, args, index, uses })
+	}
+}
+
 /// This function checks that the `system` pallet is the first pallet included in the `Runtime`
 /// struct. We make many assumptions about the `system` pallet in order to keep these macros simple.
 /// For example, we assume that the system pallet has no callable functions, and that it contains
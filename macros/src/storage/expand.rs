@@ -0,0 +1,54 @@
+use super::parse::{FieldKind, StorageDef};
+use quote::quote;
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_storage(def: StorageDef) -> proc_macro2::TokenStream {
+	let StorageDef { pallet_struct, generics, fields } = def;
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+	// The prefix of the storage items. An instantiable pallet, with an instance type parameter `I`,
+	// prefixes it with the instance, so that every instance has its own storage.
+	let is_instantiable = generics.type_params().any(|param| param.ident == "I");
+	let prefix = if is_instantiable {
+		quote! { &format!("{}{}", <I as crate::support::Instance>::PREFIX, PALLET) }
+	} else {
+		quote! { PALLET }
+	};
+
+	// This is how each field is initialized by the constructor.
+	let init = fields.iter().map(|field| {
+		let name = &field.name;
+		let ty = &field.ty;
+		match &field.kind {
+			FieldKind::Item { name: item_name, default } => {
+				// `get` returns this when the item is empty. It must not capture anything, so that
+				// it can be stored as a function pointer.
+				let on_empty = match default {
+					Some(default) => quote! { || #default },
+					None => quote! { Default::default },
+				};
+				quote! { #name: <#ty>::new(storage.clone(), #prefix, #item_name, #on_empty) }
+			},
+			FieldKind::Storage => quote! { #name: storage.clone() },
+			FieldKind::Other => quote! { #name: Default::default() },
+		}
+	});
+
+	// This quote block implements the constructor of the pallet, which gives every storage item a
+	// handle to the same storage. The items are stored under the `PALLET` prefix of the module.
+	quote! {
+		impl #impl_generics #pallet_struct #ty_generics #where_clause {
+			pub fn new(storage: crate::storage::Storage) -> Self {
+				Self {
+					#( #init ),*
+				}
+			}
+		}
+
+		impl #impl_generics crate::storage::FromStorage for #pallet_struct #ty_generics #where_clause {
+			fn from_storage(storage: crate::storage::Storage) -> Self {
+				Self::new(storage)
+			}
+		}
+	}
+}
@@ -0,0 +1,25 @@
+pub mod expand;
+pub mod parse;
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn storage(
+	_attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	let mut item_mod = syn::parse_macro_input!(item as syn::Item);
+
+	// First we parse the storage items of the pallet struct, which also removes the
+	// `#[default(..)]` attributes from `item_mod`...
+	let generated = match parse::StorageDef::try_from(&mut item_mod) {
+		// ..then we generate our new code.
+		Ok(def) => expand::expand_storage(def),
+		Err(e) => e.to_compile_error(),
+	};
+
+	// Our final product contains all of our old code, followed by the generated code.
+	quote::quote! {
+		#item_mod
+		#generated
+	}
+	.into()
+}
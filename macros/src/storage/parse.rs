@@ -0,0 +1,104 @@
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the pallet struct.
+#[derive(Debug)]
+pub struct StorageDef {
+	/// This is the name of the pallet struct. We mostly assume it is `Pallet`.
+	pub pallet_struct: syn::Ident,
+	/// The generics of the pallet struct, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// This is a list of the fields of the pallet struct. See `FieldDef`.
+	pub fields: Vec<FieldDef>,
+}
+
+/// This is what we keep about each field of the pallet struct.
+#[derive(Debug)]
+pub struct FieldDef {
+	/// The field name.
+	pub name: syn::Ident,
+	/// The type of the field.
+	pub ty: syn::Type,
+	/// How the field is initialized by the constructor. See `FieldKind`.
+	pub kind: FieldKind,
+}
+
+#[derive(Debug)]
+pub enum FieldKind {
+	/// A `StorageValue`, `StorageMap` or `StorageDoubleMap`, stored under `name`.
+	Item {
+		/// The name of the item in storage: the field name in `UpperCamelCase`.
+		name: String,
+		/// The expression given in the `#[default(..)]` attribute of the field, if any.
+		default: Option<syn::Expr>,
+	},
+	/// The `storage::Storage` handle itself.
+	Storage,
+	/// Any other field, which starts with its default value.
+	Other,
+}
+
+impl StorageDef {
+	/// Parse the fields of the pallet struct in `item`. The `#[default(..)]` attributes are removed
+	/// from the fields, since they are not real attributes.
+	pub fn try_from(item: &mut syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing a `struct`.
+		let item_struct = if let syn::Item::Struct(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet storage, expected item struct"))
+		};
+
+		let pallet_struct = item_struct.ident.clone();
+		let generics = item_struct.generics.clone();
+
+		let mut fields = vec![];
+		for field in item_struct.fields.iter_mut() {
+			let name = if let Some(name) = &field.ident {
+				name.clone()
+			} else {
+				let msg = "Invalid pallet storage, fields must be named";
+				return Err(syn::Error::new(field.span(), msg))
+			};
+
+			// The default value of an item is only known to the storage macro.
+			let default_attr = field.attrs.iter().position(|attr| attr.path().is_ident("default"));
+			let default = match default_attr {
+				Some(i) => Some(field.attrs.remove(i).parse_args::<syn::Expr>()?),
+				None => None,
+			};
+
+			let kind = match (type_name(&field.ty).as_deref(), default) {
+				(Some("StorageValue" | "StorageMap" | "StorageDoubleMap"), default) =>
+					FieldKind::Item { name: upper_camel_case(&name.to_string()), default },
+				(_, Some(_)) => {
+					let msg = "Invalid pallet storage, only storage items can have a `#[default(..)]`";
+					return Err(syn::Error::new(field.span(), msg))
+				},
+				(Some("Storage"), None) => FieldKind::Storage,
+				(_, None) => FieldKind::Other,
+			};
+
+			fields.push(FieldDef { name, ty: field.ty.clone(), kind });
+		}
+
+		Ok(Self { pallet_struct, generics, fields })
+	}
+}
+
+/// The name of the type `ty`, without its path or generics.
+fn type_name(ty: &syn::Type) -> Option<String> {
+	match ty {
+		syn::Type::Path(tp) => tp.path.segments.last().map(|segment| segment.ident.to_string()),
+		_ => None,
+	}
+}
+
+/// Convert a `snake_case` name to `UpperCamelCase`.
+fn upper_camel_case(name: &str) -> String {
+	name.split('_')
+		.map(|word| {
+			let mut chars = word.chars();
+			chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+		})
+		.collect()
+}
@@ -1,80 +1,1070 @@
-use std::collections::BTreeMap;
-use num::traits::{CheckedAdd, CheckedSub, Zero};
+use core::fmt::Debug;
+use num::traits::{CheckedAdd, CheckedSub, Saturating, Zero};
+use crate::codec::{Decode, Encode};
+use crate::storage::{Storage, StorageDoubleMap, StorageMap, StorageValue, ValueQuery};
+use crate::support::{
+    ArithmeticError, BalanceStatus, Currency, DispatchError, DispatchResult, ExistenceRequirement, GenesisError,
+    Instance, LockIdentifier, LockableCurrency, PalletInfo, ReservableCurrency, WithdrawReasons,
+};
+use crate::system::{ensure_root, ensure_signed};
 
-pub trait Config: crate::system::Config {
-	type Balance: Zero + CheckedSub + CheckedAdd + Copy;
+/// The configuration of an instance `I` of the pallet. Every instance has its own `Config`, so
+/// instances can hold different currencies.
+pub trait Config<I: Instance = ()>: crate::system::Config {
+	type Balance: Debug + Zero + CheckedSub + CheckedAdd + Saturating + Copy + Ord + Encode + Decode;
+	/// The smallest total balance an account can hold. An account whose balance drops below it is
+	/// reaped, and the rest of its balance, its dust, is burned.
+	const EXISTENTIAL_DEPOSIT: Self::Balance;
 }
 
+/// The events emitted by the balances pallet.
+#[macros::event(generate_deposit)]
+#[derive(Debug, macros::CloneNoBound, PartialEq)]
+pub enum Event<T: Config<I>, I: Instance = ()> {
+    /// `amount` was transferred from `from` to `to`.
+    Transfer { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+    /// `amount` was transferred from `from` to `to`, leaving `from` at least the existential deposit.
+    TransferKeepAlive { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+    /// All the transferable funds of `from`, `amount`, were transferred to `to`. With `keep_alive`,
+    /// `from` kept the existential deposit.
+    TransferAll { from: T::AccountId, to: T::AccountId, amount: T::Balance, keep_alive: bool },
+    /// `amount` was transferred from `from` to `to` by root.
+    ForceTransfer { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+    /// The balance of `who` was set to `amount` by root.
+    BalanceSet { who: T::AccountId, amount: T::Balance },
+    /// `amount` was moved from the free to the reserved balance of `who`.
+    Reserved { who: T::AccountId, amount: T::Balance },
+    /// `amount` was moved from the reserved to the free balance of `who`.
+    Unreserved { who: T::AccountId, amount: T::Balance },
+    /// `amount` was moved from the reserved balance of `from` to the balance of `to` with
+    /// `destination_status`.
+    ReserveRepatriated { from: T::AccountId, to: T::AccountId, amount: T::Balance, destination_status: BalanceStatus },
+    /// `from` made `count` transfers at once, of `total` together. Each of them has its own
+    /// `Transfer` event. Transfers of a batch to `from` itself move nothing, so they are not counted.
+    BatchTransferred { from: T::AccountId, count: u32, total: T::Balance },
+    /// `account` was reaped, and its remaining `amount` burned.
+    DustLost { account: T::AccountId, amount: T::Balance },
+}
+
+/// The errors returned by the balances pallet.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The caller does not have enough funds for the transfer.
+    InsufficientBalance,
+    /// The funds are there, but a lock forbids using them this way.
+    LiquidityRestrictions,
+    /// The account would drop below the existential deposit, but it must be kept alive.
+    KeepAlive,
+    /// The amount is too small to create the account, which does not exist yet.
+    ExistentialDeposit,
+    /// There is nothing to transfer: no funds can be moved, or no transfer was given.
+    NothingToTransfer,
+}
+
+/// The genesis state of the balances pallet.
+#[derive(Debug, macros::CloneNoBound, serde::Deserialize)]
+#[serde(
+    bound(deserialize = "T::AccountId: serde::Deserialize<'de>, T::Balance: serde::Deserialize<'de>"),
+    deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config<I>, I: Instance = ()> {
+    /// The accounts which hold a free balance at genesis, and their balance.
+    pub balances: Vec<(T::AccountId, T::Balance)>,
+    /// The funds locked at genesis under `GENESIS_LOCK`, for example those which are not vested
+    /// yet: the account, the amount locked and the uses it forbids. The locks of the same account
+    /// are combined.
+    #[serde(default)]
+    pub locks: Vec<(T::AccountId, T::Balance, WithdrawReasons)>,
+}
+
+impl<T: Config<I>, I: Instance> Default for GenesisConfig<T, I> {
+    fn default() -> Self {
+        Self { balances: Vec::new(), locks: Vec::new() }
+    }
+}
+
+/// The identifier of the locks set at genesis.
+pub const GENESIS_LOCK: LockIdentifier = *b"genesis ";
+
+/// The balance of an account, split between the funds it can use and those set aside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountData<Balance> {
+    /// The funds which can be spent, as far as the locks on the account allow.
+    pub free: Balance,
+    /// The funds set aside, for example as a deposit, which cannot be spent until unreserved.
+    pub reserved: Balance,
+}
+
+impl<Balance: Zero> Default for AccountData<Balance> {
+    fn default() -> Self {
+        Self { free: Balance::zero(), reserved: Balance::zero() }
+    }
+}
+
+impl<Balance: Encode> Encode for AccountData<Balance> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        self.free.encode_to(dest);
+        self.reserved.encode_to(dest);
+    }
+}
+
+impl<Balance: Decode> Decode for AccountData<Balance> {
+    fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+        Ok(Self { free: Decode::decode(input)?, reserved: Decode::decode(input)? })
+    }
+}
+
+/// A lock on the free balance of an account, which keeps it from dropping below `amount` for the
+/// uses forbidden by `reasons`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceLock<Balance> {
+    pub amount: Balance,
+    pub reasons: WithdrawReasons,
+}
+
+impl<Balance: Encode> Encode for BalanceLock<Balance> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+        self.amount.encode_to(dest);
+        self.reasons.encode_to(dest);
+    }
+}
+
+impl<Balance: Decode> Decode for BalanceLock<Balance> {
+    fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+        Ok(Self { amount: Decode::decode(input)?, reasons: Decode::decode(input)? })
+    }
+}
+
+const PALLET: &str = "Balances";
+
+#[macros::storage]
 #[derive(Debug)]
-pub struct Pallet<T: Config> {
-    balances: BTreeMap<T::AccountId, T::Balance>,
+pub struct Pallet<T: Config<I>, I: Instance = ()> {
+    /// The storage handle shared by every pallet of the runtime, to count accounts in the system
+    /// pallet and to deposit events.
+    storage: Storage,
+    /// The sum of the balances of all accounts.
+    #[default(T::Balance::zero())]
+    total_issuance: StorageValue<T::Balance, ValueQuery>,
+    /// The free and reserved balance of every account.
+    #[default(AccountData::default())]
+    accounts: StorageMap<T::AccountId, AccountData<T::Balance>, ValueQuery>,
+    /// The locks on the free balance of every account, by the identifier of each lock.
+    locks: StorageDoubleMap<T::AccountId, LockIdentifier, BalanceLock<T::Balance>>,
+}
+
+impl<T: Config<I>, I: Instance> Pallet<T, I> {
+    /// The funds of `who` which can be spent, as far as the locks on the account allow.
+    pub fn free_balance(&self, who: &T::AccountId) -> T::Balance {
+        self.accounts.get(who).free
+    }
+
+    /// The sum of the balances of all accounts.
+    pub fn total_issuance(&self) -> T::Balance {
+        self.total_issuance.get()
+    }
+
+    /// The locks on the free balance of `who`, by their identifier.
+    pub fn locks(&self, who: &T::AccountId) -> Vec<(LockIdentifier, BalanceLock<T::Balance>)> {
+        self.locks.iter_prefix(who)
+    }
+
+    /// Build the genesis state of the pallet. Every account may only be endowed once, with at least
+    /// the existential deposit, and the sum of all balances must not overflow. Funds may only be
+    /// locked in accounts which are endowed.
+    pub fn build_genesis(&mut self, config: &GenesisConfig<T, I>) -> Result<(), GenesisError> {
+        let mut total = T::Balance::zero();
+        for (who, amount) in &config.balances {
+            if config.balances.iter().filter(|(other, _)| other == who).count() > 1 {
+                return Err(GenesisError::DuplicateAccount)
+            }
+            if *amount < T::EXISTENTIAL_DEPOSIT {
+                return Err(GenesisError::BelowExistentialDeposit)
+            }
+            total = total.checked_add(amount).ok_or(GenesisError::TotalOverflow)?;
+            self.set_free(who, *amount, ExistenceRequirement::AllowDeath)
+                .expect("the account is new and holds the existential deposit");
+        }
+        self.total_issuance.put(&total);
+        for (who, amount, reasons) in &config.locks {
+            self.extend_lock(GENESIS_LOCK, who, *amount, *reasons).map_err(|_| GenesisError::UnknownAccount)?;
+        }
+        Ok(())
+    }
+
+    /// The system pallet, which counts the accounts this pallet provides for.
+    fn system(&self) -> crate::system::Pallet<T> {
+        crate::system::Pallet::new(self.storage.clone())
+    }
+
+    /// Change the balance of `who` with `f`, creating the account if it reaches the existential
+    /// deposit, or reaping it and burning its dust if it drops below. Nothing is changed if `f`
+    /// fails, or if the account must be reaped but `existence` or other pallets keep it alive.
+    fn mutate_account<R>(
+        &mut self,
+        who: &T::AccountId,
+        existence: ExistenceRequirement,
+        f: impl FnOnce(&mut AccountData<T::Balance>) -> Result<R, DispatchError>,
+    ) -> Result<R, DispatchError> {
+        let existed = self.accounts.contains_key(who);
+        let mut account = self.accounts.get(who);
+        let result = f(&mut account)?;
+        let total = account.free.checked_add(&account.reserved).ok_or(ArithmeticError::Overflow)?;
+        if total >= T::EXISTENTIAL_DEPOSIT {
+            if !existed {
+                self.system().inc_providers(who);
+            }
+            self.accounts.insert(who, &account);
+        } else if existed {
+            if existence == ExistenceRequirement::KeepAlive || !self.system().can_dec_providers(who) {
+                return Err(Error::KeepAlive.into())
+            }
+            self.system().dec_providers(who)?;
+            self.accounts.remove(who);
+            if !total.is_zero() {
+                self.total_issuance.mutate(|issuance| *issuance = issuance.saturating_sub(total));
+                self.deposit_event(Event::DustLost { account: who.clone(), amount: total });
+            }
+        } else if !total.is_zero() {
+            return Err(Error::ExistentialDeposit.into())
+        }
+        Ok(result)
+    }
+
+    fn set_free(&mut self, who: &T::AccountId, amount: T::Balance, existence: ExistenceRequirement) -> DispatchResult {
+        self.mutate_account(who, existence, |account| {
+            account.free = amount;
+            Ok(())
+        })
+    }
+
+    /// The amount of the free balance of `who` which the locks forbidding `reasons` keep.
+    fn frozen(&self, who: &T::AccountId, reasons: WithdrawReasons) -> T::Balance {
+        self.locks(who)
+            .into_iter()
+            .filter(|(_, lock)| lock.reasons.intersects(reasons))
+            .map(|(_, lock)| lock.amount)
+            .max()
+            .unwrap_or_else(T::Balance::zero)
+    }
+
+    /// Check that the free balance of `who` can drop to `new_free` for `reasons`.
+    fn ensure_can_withdraw(&self, who: &T::AccountId, new_free: T::Balance, reasons: WithdrawReasons) -> DispatchResult {
+        if new_free < self.frozen(who, reasons) {
+            return Err(Error::LiquidityRestrictions.into())
+        }
+        Ok(())
+    }
+
+    /// Create `amount` in the free balance of `who`, and add it to the total issuance. Fails if the
+    /// total issuance would overflow, or if the account does not exist and `amount` is below the
+    /// existential deposit.
+    pub fn mint_into(&mut self, who: &T::AccountId, amount: T::Balance) -> DispatchResult {
+        let total = self.total_issuance().checked_add(&amount).ok_or(ArithmeticError::Overflow)?;
+        self.deposit(who, amount)?;
+        self.total_issuance.put(&total);
+        Ok(())
+    }
+
+    /// Destroy `amount` of the free balance of `who` for `reasons`, for example to pay a fee, and
+    /// remove it from the total issuance. `existence` tells whether this may reap the account.
+    pub fn burn_from(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+        reasons: WithdrawReasons,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        self.withdraw(who, amount, reasons, existence)?;
+        self.total_issuance.mutate(|total| *total = total.saturating_sub(amount));
+        Ok(())
+    }
+
+    /// Take `amount` out of the free balance of `who` for `reasons`, leaving the total issuance to
+    /// the caller. `existence` tells whether this may reap the account.
+    fn withdraw(
+        &mut self,
+        who: &T::AccountId,
+        amount: T::Balance,
+        reasons: WithdrawReasons,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        let new_free = self.free_balance(who).checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+        self.ensure_can_withdraw(who, new_free, reasons)?;
+        self.set_free(who, new_free, existence)
+    }
+
+    /// Add `amount` to the free balance of `who`, leaving the total issuance to the caller. Fails if
+    /// the account does not exist and `amount` is below the existential deposit.
+    fn deposit(&mut self, who: &T::AccountId, amount: T::Balance) -> DispatchResult {
+        self.mutate_account(who, ExistenceRequirement::AllowDeath, |account| {
+            account.free = account.free.checked_add(&amount).ok_or(ArithmeticError::Overflow)?;
+            Ok(())
+        })
+    }
+
+    /// Move `amount` from `from` to `to`, whoever asked for it, and emit `event`. The locks of `from`
+    /// are respected, and `existence` tells whether this may reap it.
+    fn do_transfer(
+        &mut self,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: T::Balance,
+        existence: ExistenceRequirement,
+        event: Event<T, I>,
+    ) -> DispatchResult {
+        // Moving funds to the same account changes nothing, and must not reap it on the way, but
+        // it fails whenever moving them to another account would.
+        if from == to {
+            let account = self.accounts.get(from);
+            let new_free = account.free.checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+            self.ensure_can_withdraw(from, new_free, WithdrawReasons::Transfer)?;
+            let reaped = new_free.saturating_add(account.reserved) < T::EXISTENTIAL_DEPOSIT;
+            if existence == ExistenceRequirement::KeepAlive && reaped && self.accounts.contains_key(from) {
+                return Err(Error::KeepAlive.into())
+            }
+            return Ok(())
+        }
+        // Nothing is changed unless `to` can take the funds, not even the dust of a reaped `from`.
+        self.transactional(|pallet| {
+            pallet.withdraw(from, amount, WithdrawReasons::Transfer, existence)?;
+            pallet.deposit(to, amount)
+        })?;
+        self.deposit_event(event);
+        Ok(())
+    }
+
+    /// Run `f` in a storage transaction. If it fails, none of its changes are kept, and neither are
+    /// the events it emitted.
+    fn transactional<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<R, DispatchError>,
+    ) -> Result<R, DispatchError> {
+        self.storage.clone().with_transaction(|| f(self))
+    }
+
+    /// Run `f` for another pallet, tagging the errors of this pallet it returns with the index of
+    /// this pallet. The runtime only tags the errors of the pallet whose call it dispatched.
+    fn tag_errors<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<R, DispatchError>,
+    ) -> Result<R, DispatchError> {
+        f(self).map_err(|error| match <T::PalletInfo as PalletInfo>::index::<Self>() {
+            Some(index) => error.with_pallet_index(index),
+            None => error,
+        })
+    }
+
+    /// The part of the free balance of `who` which can be withdrawn for `reasons`: what the locks
+    /// forbidding them do not keep, and, if `existence` asks to keep the account alive, what it
+    /// does not need to hold the existential deposit.
+    pub fn reducible_balance(
+        &self,
+        who: &T::AccountId,
+        reasons: WithdrawReasons,
+        existence: ExistenceRequirement,
+    ) -> T::Balance {
+        let account = self.accounts.get(who);
+        let mut kept = self.frozen(who, reasons);
+        if existence == ExistenceRequirement::KeepAlive {
+            kept = kept.max(T::EXISTENTIAL_DEPOSIT.saturating_sub(account.reserved));
+        }
+        account.free.saturating_sub(kept)
+    }
+
+}
+
+impl<T: Config<I>, I: Instance> Currency for Pallet<T, I> {
+    type AccountId = T::AccountId;
+    type Balance = T::Balance;
+
+    fn free_balance(&self, who: &T::AccountId) -> T::Balance {
+        Pallet::free_balance(self, who)
+    }
+
+    fn total_issuance(&self) -> T::Balance {
+        Pallet::total_issuance(self)
+    }
+
+    fn minimum_balance(&self) -> T::Balance {
+        T::EXISTENTIAL_DEPOSIT
+    }
+
+    fn transfer(
+        &mut self,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: T::Balance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        let event = Event::Transfer { from: from.clone(), to: to.clone(), amount };
+        self.tag_errors(|pallet| pallet.do_transfer(from, to, amount, existence, event))
+    }
 }
 
-impl<T: Config> Pallet<T>
-{
-    pub fn new() -> Self {
-        Self {
-            balances: BTreeMap::new(), //returning new instance of balances
+impl<T: Config<I>, I: Instance> ReservableCurrency for Pallet<T, I> {
+    fn reserved_balance(&self, who: &T::AccountId) -> T::Balance {
+        self.accounts.get(who).reserved
+    }
+
+    fn can_reserve(&self, who: &T::AccountId, amount: T::Balance) -> bool {
+        self.free_balance(who)
+            .checked_sub(&amount)
+            .is_some_and(|new_free| self.ensure_can_withdraw(who, new_free, WithdrawReasons::Transfer).is_ok())
+    }
+
+    fn reserve(&mut self, who: &T::AccountId, amount: T::Balance) -> DispatchResult {
+        self.tag_errors(|pallet| {
+            if amount.is_zero() {
+                return Ok(())
+            }
+            let new_free = pallet.free_balance(who).checked_sub(&amount).ok_or(Error::InsufficientBalance)?;
+            pallet.ensure_can_withdraw(who, new_free, WithdrawReasons::Transfer)?;
+            pallet.mutate_account(who, ExistenceRequirement::AllowDeath, |account| {
+                account.reserved = account.reserved.checked_add(&amount).ok_or(ArithmeticError::Overflow)?;
+                account.free = new_free;
+                Ok(())
+            })?;
+            pallet.deposit_event(Event::Reserved { who: who.clone(), amount });
+            Ok(())
+        })
+    }
+
+    fn unreserve(&mut self, who: &T::AccountId, amount: T::Balance) -> T::Balance {
+        if amount.is_zero() || !self.accounts.contains_key(who) {
+            return amount
+        }
+        let result = self.mutate_account(who, ExistenceRequirement::AllowDeath, |account| {
+            let actual = amount.min(account.reserved);
+            account.free = account.free.checked_add(&actual).ok_or(ArithmeticError::Overflow)?;
+            account.reserved = account.reserved - actual;
+            Ok(actual)
+        });
+        // The free balance cannot hold the unreserved funds, so they stay reserved.
+        let Ok(actual) = result else { return amount };
+        if !actual.is_zero() {
+            self.deposit_event(Event::Unreserved { who: who.clone(), amount: actual });
+        }
+        amount - actual
+    }
+
+    fn repatriate_reserved(
+        &mut self,
+        slashed: &T::AccountId,
+        beneficiary: &T::AccountId,
+        amount: T::Balance,
+        status: BalanceStatus,
+    ) -> Result<T::Balance, DispatchError> {
+        if slashed == beneficiary {
+            return Ok(match status {
+                BalanceStatus::Free => self.unreserve(slashed, amount),
+                BalanceStatus::Reserved => amount - amount.min(self.reserved_balance(slashed)),
+            })
+        }
+        self.tag_errors(|pallet| {
+            let actual = amount.min(pallet.reserved_balance(slashed));
+            // `beneficiary` is credited first, so that nothing is changed if it cannot take the funds.
+            pallet.storage.clone().with_transaction(|| {
+                pallet.mutate_account(beneficiary, ExistenceRequirement::AllowDeath, |to| {
+                    let destination = match status {
+                        BalanceStatus::Free => &mut to.free,
+                        BalanceStatus::Reserved => &mut to.reserved,
+                    };
+                    *destination = destination.checked_add(&actual).ok_or(ArithmeticError::Overflow)?;
+                    Ok(())
+                })?;
+                pallet.mutate_account(slashed, ExistenceRequirement::AllowDeath, |from| {
+                    from.reserved = from.reserved - actual;
+                    Ok(())
+                })
+            })?;
+            if !actual.is_zero() {
+                pallet.deposit_event(Event::ReserveRepatriated {
+                    from: slashed.clone(),
+                    to: beneficiary.clone(),
+                    amount: actual,
+                    destination_status: status,
+                });
+            }
+            Ok(amount - actual)
+        })
+    }
+}
+
+impl<T: Config<I>, I: Instance> LockableCurrency for Pallet<T, I> {
+    fn set_lock(
+        &mut self,
+        id: LockIdentifier,
+        who: &T::AccountId,
+        amount: T::Balance,
+        reasons: WithdrawReasons,
+    ) -> DispatchResult {
+        if amount.is_zero() {
+            self.remove_lock(id, who);
+            return Ok(())
         }
+        // The locks of an account keep it alive, as long as it has any.
+        if self.locks(who).is_empty() {
+            self.system().inc_consumers(who)?;
+        }
+        self.locks.insert(who, &id, &BalanceLock { amount, reasons });
+        Ok(())
     }
 
-    pub fn set_balance(&mut self, who: &T::AccountId, amount: T::Balance) {
-        self.balances.insert(who.clone(), amount);
+    fn extend_lock(
+        &mut self,
+        id: LockIdentifier,
+        who: &T::AccountId,
+        amount: T::Balance,
+        reasons: WithdrawReasons,
+    ) -> DispatchResult {
+        let lock = match self.locks.get(who, &id) {
+            Some(lock) => BalanceLock { amount: lock.amount.max(amount), reasons: lock.reasons.union(reasons) },
+            None => BalanceLock { amount, reasons },
+        };
+        self.set_lock(id, who, lock.amount, lock.reasons)
     }
 
-    pub fn balance(&self, who: &T::AccountId) -> T::Balance {
-        *self.balances.get(who).unwrap_or(&T::Balance::zero())
+    fn remove_lock(&mut self, id: LockIdentifier, who: &T::AccountId) {
+        if !self.locks.contains_key(who, &id) {
+            return
+        }
+        self.locks.remove(who, &id);
+        if self.locks(who).is_empty() {
+            self.system().dec_consumers(who);
+        }
     }
+}
 
+// The balances pallet has no per-block logic.
+impl<T: Config<I>, I: Instance> crate::support::Hooks<T::BlockNumber> for Pallet<T, I> {
+    fn try_state(&self) -> Result<(), &'static str> {
+        let total = self.accounts.iter().into_iter().try_fold(T::Balance::zero(), |total, (_, account)| {
+            total.checked_add(&account.free)?.checked_add(&account.reserved)
+        });
+        if total != Some(self.total_issuance()) {
+            return Err("the balances of all accounts do not add up to the total issuance")
+        }
+        Ok(())
+    }
 }
 
+// The balances pallet does not dispatch other calls.
+impl<T: Config<I>, I: Instance, O, C> crate::support::DispatchNested<O, C> for Pallet<T, I> {}
+
 #[macros::call]
-impl<T: Config> Pallet<T> {
-    //#[call]
-    pub fn transfer(&mut self, caller: T::AccountId, to: T::AccountId, amount: T::Balance) -> crate::support::DispatchResult{
-        let caller_balance = self.balance(&caller);
-		let to_balance = self.balance(&to);
+impl<T: Config<I>, I: Instance> Pallet<T, I> {
+    /// Set the free balance of `who` to `amount`. Can only be called by root.
+    #[call_index(0)]
+    #[weight(10_000)]
+    pub fn force_set_balance(&mut self, origin: T::RuntimeOrigin, who: T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+        ensure_root(origin)?;
+        let old = self.free_balance(&who);
+        if amount > old {
+            self.total_issuance().checked_add(&(amount - old)).ok_or(ArithmeticError::Overflow)?;
+        }
+        self.set_free(&who, amount, ExistenceRequirement::AllowDeath)?;
+        // The difference is minted or burned. The dust of a reaped account is burned on its own.
+        self.total_issuance.mutate(|issuance| {
+            *issuance = if amount > old {
+                issuance.saturating_add(amount - old)
+            } else {
+                issuance.saturating_sub(old - amount)
+            }
+        });
+        self.deposit_event(Event::BalanceSet { who, amount });
+        Ok(())
+    }
+
+    /// Transfer `amount` from the caller to `to`.
+    #[call_index(1)]
+    #[weight(20_000)]
+    pub fn transfer(&mut self, origin: T::RuntimeOrigin, to: T::AccountId, amount: T::Balance) -> crate::support::DispatchResult{
+        let caller = ensure_signed(origin)?;
+        let event = Event::Transfer { from: caller.clone(), to: to.clone(), amount };
+        self.do_transfer(&caller, &to, amount, ExistenceRequirement::AllowDeath, event)
+    }
 
-        let new_caller_balance = caller_balance.checked_sub(&amount).ok_or("Not enough funds.")?;
-        let new_to_balance = to_balance.checked_add(&amount).ok_or("Error.")?;
+    /// Transfer `amount` from `from` to `to`. Can only be called by root.
+    #[call_index(2)]
+    #[weight(20_000)]
+    pub fn force_transfer(&mut self, origin: T::RuntimeOrigin, from: T::AccountId, to: T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+        ensure_root(origin)?;
+        let event = Event::ForceTransfer { from: from.clone(), to: to.clone(), amount };
+        self.do_transfer(&from, &to, amount, ExistenceRequirement::AllowDeath, event)
+    }
+
+    /// Transfer `amount` from the caller to `to`, unless that would reap the caller.
+    #[call_index(3)]
+    #[weight(20_000)]
+    pub fn transfer_keep_alive(&mut self, origin: T::RuntimeOrigin, to: T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+        let caller = ensure_signed(origin)?;
+        let event = Event::TransferKeepAlive { from: caller.clone(), to: to.clone(), amount };
+        self.do_transfer(&caller, &to, amount, ExistenceRequirement::KeepAlive, event)
+    }
+
+    /// Transfer all the funds the caller can to `to`. With `keep_alive`, the caller keeps the
+    /// existential deposit, and otherwise the caller is reaped if nothing else keeps it alive.
+    #[call_index(4)]
+    #[weight(20_000)]
+    pub fn transfer_all(&mut self, origin: T::RuntimeOrigin, to: T::AccountId, keep_alive: bool) -> crate::support::DispatchResult {
+        let caller = ensure_signed(origin)?;
+        let existence =
+            if keep_alive { ExistenceRequirement::KeepAlive } else { ExistenceRequirement::AllowDeath };
+        let amount = self.reducible_balance(&caller, WithdrawReasons::Transfer, existence);
+        if amount.is_zero() {
+            return Err(Error::NothingToTransfer.into())
+        }
+        let event = Event::TransferAll { from: caller.clone(), to: to.clone(), amount, keep_alive };
+        self.do_transfer(&caller, &to, amount, existence, event)
+    }
 
-        self.balances.insert(caller, new_caller_balance);
-		self.balances.insert(to, new_to_balance);
+    /// Make every transfer of `transfers` from the caller, in order. Either all of them are made,
+    /// or none is.
+    #[call_index(5)]
+    #[weight(10_000u64.saturating_add(20_000u64.saturating_mul(transfers.len() as u64)))]
+    pub fn transfer_many(&mut self, origin: T::RuntimeOrigin, transfers: Vec<(T::AccountId, T::Balance)>) -> crate::support::DispatchResult {
+        let caller = ensure_signed(origin)?;
+        if transfers.is_empty() {
+            return Err(Error::NothingToTransfer.into())
+        }
+        let (count, total) = self.transactional(|pallet| {
+            let (mut count, mut total) = (0u32, T::Balance::zero());
+            for (to, amount) in transfers {
+                let event = Event::Transfer { from: caller.clone(), to: to.clone(), amount };
+                pallet.do_transfer(&caller, &to, amount, ExistenceRequirement::AllowDeath, event)?;
+                // A transfer to the caller itself is checked like any other, but moves nothing.
+                if to != caller {
+                    count += 1;
+                    total = total.checked_add(&amount).ok_or(ArithmeticError::Overflow)?;
+                }
+            }
+            Ok((count, total))
+        })?;
+        self.deposit_event(Event::BatchTransferred { from: caller, count, total });
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::support::{ArithmeticError, DispatchError, Origin};
+
+    #[derive(Debug, PartialEq)]
     struct TestConfig;
 
     impl crate::system::Config for TestConfig {
 		type AccountId = String;
 		type BlockNumber = u32;
 		type Nonce = u32;
+		type Signature = crate::support::TestSignature<String>;
+		type RuntimeOrigin = Origin<String>;
+		type RuntimeEvent = ();
+		type PalletInfo = ();
+		const BLOCK_WEIGHT_LIMIT: crate::support::Weight = u64::MAX;
 	}
 
 	impl super::Config for TestConfig {
 		type Balance = u128;
+		const EXISTENTIAL_DEPOSIT: u128 = 10;
 	}
 
     #[test]
     fn init_balances() {
-        let mut balances = super::Pallet::<TestConfig>::new();
-        assert_eq!(balances.balance(&"alice".to_string()), 0);
-        balances.set_balance(&"alice".to_string(), 100);
-        assert_eq!(balances.balance(&"alice".to_string()), 100);
-        assert_eq!(balances.balance(&"bob".to_string()), 0);
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        assert_eq!(balances.free_balance(&"alice".to_string()), 0);
+        assert_eq!(
+            balances.force_set_balance(Origin::Signed("alice".to_string()), "alice".to_string(), 100),
+            Err(DispatchError::BadOrigin)
+        );
+        balances.force_set_balance(Origin::Root, "alice".to_string(), 100).unwrap();
+        assert_eq!(balances.free_balance(&"alice".to_string()), 100);
+        assert_eq!(balances.free_balance(&"bob".to_string()), 0);
+    }
+
+    #[test]
+    fn genesis_config() {
+        use crate::support::{GenesisError, WithdrawReasons};
+        use super::GenesisConfig;
+
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        let config = GenesisConfig::<TestConfig> {
+            balances: vec![("alice".to_string(), 100), ("bob".to_string(), 50)],
+            locks: vec![
+                ("alice".to_string(), 30, WithdrawReasons::Fee),
+                ("alice".to_string(), 60, WithdrawReasons::Transfer),
+            ],
+        };
+        assert_eq!(balances.build_genesis(&config), Ok(()));
+        assert_eq!(balances.free_balance(&"alice".to_string()), 100);
+        assert_eq!(balances.free_balance(&"bob".to_string()), 50);
+        // The locks of alice were combined into one.
+        let lock = super::BalanceLock { amount: 60, reasons: WithdrawReasons::All };
+        assert_eq!(balances.locks(&"alice".to_string()), vec![(super::GENESIS_LOCK, lock)]);
+        assert_eq!(balances.locks(&"bob".to_string()), vec![]);
+
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        let config = GenesisConfig::<TestConfig> {
+            balances: vec![("alice".to_string(), 100), ("alice".to_string(), 50)],
+            ..Default::default()
+        };
+        assert_eq!(balances.build_genesis(&config), Err(GenesisError::DuplicateAccount));
+        let config = GenesisConfig::<TestConfig> {
+            balances: vec![("alice".to_string(), u128::MAX), ("bob".to_string(), 10)],
+            ..Default::default()
+        };
+        assert_eq!(balances.build_genesis(&config), Err(GenesisError::TotalOverflow));
+        let config = GenesisConfig::<TestConfig> { balances: vec![("alice".to_string(), 9)], ..Default::default() };
+        assert_eq!(balances.build_genesis(&config), Err(GenesisError::BelowExistentialDeposit));
+        let config = GenesisConfig::<TestConfig> {
+            balances: vec![("alice".to_string(), 100)],
+            locks: vec![("bob".to_string(), 10, WithdrawReasons::All)],
+        };
+        assert_eq!(balances.build_genesis(&config), Err(GenesisError::UnknownAccount));
+    }
+
+    #[test]
+    fn self_transfer_changes_nothing() {
+        use crate::support::{LockableCurrency, WithdrawReasons};
+
+        let alice = "alice".to_string();
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        balances.force_set_balance(Origin::Root, alice.clone(), 100).unwrap();
+        balances.take_events();
+
+        // Moving everything but dust to itself must not reap the account and burn the dust.
+        assert_eq!(balances.transfer(Origin::Signed(alice.clone()), alice.clone(), 95), Ok(()));
+        assert_eq!(balances.transfer_all(Origin::Signed(alice.clone()), alice.clone(), false), Ok(()));
+        assert_eq!(balances.free_balance(&alice), 100);
+        assert_eq!(balances.total_issuance(), 100);
+        assert_eq!(balances.take_events(), vec![]);
+
+        // It still fails when a transfer to another account would.
+        assert_eq!(
+            balances.transfer(Origin::Signed(alice.clone()), alice.clone(), 101),
+            Err(super::Error::InsufficientBalance.into())
+        );
+        assert_eq!(
+            balances.transfer_keep_alive(Origin::Signed(alice.clone()), alice.clone(), 95),
+            Err(super::Error::KeepAlive.into())
+        );
+        balances.set_lock(*b"staking ", &alice, 50, WithdrawReasons::Transfer).unwrap();
+        assert_eq!(
+            balances.transfer(Origin::Signed(alice.clone()), alice.clone(), 60),
+            Err(super::Error::LiquidityRestrictions.into())
+        );
+        assert_eq!(balances.transfer(Origin::Signed(alice.clone()), alice.clone(), 50), Ok(()));
+        assert_eq!(balances.free_balance(&alice), 100);
     }
 
     #[test]
 	fn transfer_balance() {
-        let mut balances = super::Pallet::<TestConfig>::new();
-        balances.set_balance(&"alice".to_string(), 100);
-        assert_eq!(balances.transfer(&"alice".to_string(), &"bob".to_string(), 500), Err("Not enough funds."));
-        assert_eq!(balances.transfer(&"alice".to_string(), &"bob".to_string(), 50), Ok(()));
-        assert_eq!(balances.balance(&"alice".to_string()), 50);
-        assert_eq!(balances.balance(&"bob".to_string()), 50);
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        balances.force_set_balance(Origin::Root, "alice".to_string(), 100).unwrap();
+        assert_eq!(
+            balances.transfer(Origin::Signed("alice".to_string()), "bob".to_string(), 500),
+            Err(super::Error::InsufficientBalance.into())
+        );
+        assert_eq!(balances.transfer(Origin::Signed("alice".to_string()), "bob".to_string(), 50), Ok(()));
+        assert_eq!(balances.free_balance(&"alice".to_string()), 50);
+        assert_eq!(balances.free_balance(&"bob".to_string()), 50);
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                super::Event::BalanceSet { who: "alice".to_string(), amount: 100 },
+                super::Event::Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: 50 },
+            ]
+        );
+
+        // Transfers cannot overflow, since no balance exceeds the total issuance.
+        assert_eq!(
+            balances.force_set_balance(Origin::Root, "bob".to_string(), u128::MAX),
+            Err(DispatchError::Arithmetic(ArithmeticError::Overflow))
+        );
+
+        // Root can move funds between any two accounts.
+        assert_eq!(
+            balances.force_transfer(Origin::Signed("alice".to_string()), "alice".to_string(), "charlie".to_string(), 10),
+            Err(DispatchError::BadOrigin)
+        );
+        balances.force_transfer(Origin::Root, "alice".to_string(), "charlie".to_string(), 10).unwrap();
+        assert_eq!(balances.free_balance(&"alice".to_string()), 40);
+        assert_eq!(balances.free_balance(&"charlie".to_string()), 10);
+        assert_eq!(
+            balances.take_events(),
+            vec![super::Event::ForceTransfer { from: "alice".to_string(), to: "charlie".to_string(), amount: 10 }]
+        );
+    }
+
+    #[test]
+    fn reserve_and_unreserve() {
+        use crate::support::{BalanceStatus, ReservableCurrency};
+        use super::Event;
+
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        balances.force_set_balance(Origin::Root, alice.clone(), 100).unwrap();
+        balances.take_events();
+
+        assert!(!balances.can_reserve(&alice, 101));
+        assert_eq!(balances.reserve(&alice, 101), Err(super::Error::InsufficientBalance.into()));
+        assert_eq!(balances.reserve(&alice, 60), Ok(()));
+        assert_eq!((balances.free_balance(&alice), balances.reserved_balance(&alice)), (40, 60));
+
+        // Reserved funds cannot be transferred.
+        assert_eq!(
+            balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 50),
+            Err(super::Error::InsufficientBalance.into())
+        );
 
+        // Only the reserved funds can be unreserved.
+        assert_eq!(balances.unreserve(&alice, 10), 0);
+        assert_eq!(balances.unreserve(&bob, 10), 10);
+        assert_eq!((balances.free_balance(&alice), balances.reserved_balance(&alice)), (50, 50));
+
+        // Unreserving from an unknown account does not create it, so that crediting it later does.
+        assert!(!balances.accounts.contains_key(&bob));
+
+        assert_eq!(balances.repatriate_reserved(&alice, &bob, 20, BalanceStatus::Reserved), Ok(0));
+        assert_eq!(balances.repatriate_reserved(&alice, &bob, 40, BalanceStatus::Free), Ok(10));
+        // Nothing is left to move, so there is no event.
+        assert_eq!(balances.repatriate_reserved(&alice, &bob, 5, BalanceStatus::Free), Ok(5));
+        assert_eq!((balances.free_balance(&alice), balances.reserved_balance(&alice)), (50, 0));
+        assert_eq!((balances.free_balance(&bob), balances.reserved_balance(&bob)), (30, 20));
+        assert_eq!(balances.system().providers(&bob), 1);
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                Event::Reserved { who: alice.clone(), amount: 60 },
+                Event::Unreserved { who: alice.clone(), amount: 10 },
+                Event::ReserveRepatriated {
+                    from: alice.clone(),
+                    to: bob.clone(),
+                    amount: 20,
+                    destination_status: BalanceStatus::Reserved,
+                },
+                Event::ReserveRepatriated {
+                    from: alice.clone(),
+                    to: bob.clone(),
+                    amount: 30,
+                    destination_status: BalanceStatus::Free,
+                },
+            ]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn locks_limit_withdrawals() {
+        use crate::support::{ExistenceRequirement, LockableCurrency, ReservableCurrency, WithdrawReasons};
+
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        balances.force_set_balance(Origin::Root, alice.clone(), 100).unwrap();
+
+        // The largest lock which forbids a use of funds applies to it.
+        balances.set_lock(*b"staking ", &alice, 50, WithdrawReasons::Transfer).unwrap();
+        balances.set_lock(*b"vesting ", &alice, 30, WithdrawReasons::All).unwrap();
+        assert_eq!(
+            balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 51),
+            Err(super::Error::LiquidityRestrictions.into())
+        );
+        assert!(!balances.can_reserve(&alice, 51));
+        assert_eq!(balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 50), Ok(()));
+        assert_eq!(
+            balances.withdraw(&alice, 21, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive),
+            Err(super::Error::LiquidityRestrictions.into())
+        );
+        assert_eq!(balances.withdraw(&alice, 20, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive), Ok(()));
+
+        // Extending a lock never shrinks it, and removing it frees the funds.
+        balances.extend_lock(*b"staking ", &alice, 10, WithdrawReasons::Fee).unwrap();
+        assert_eq!(
+            balances.locks(&alice),
+            vec![
+                (*b"staking ", super::BalanceLock { amount: 50, reasons: WithdrawReasons::All }),
+                (*b"vesting ", super::BalanceLock { amount: 30, reasons: WithdrawReasons::All }),
+            ]
+        );
+        balances.remove_lock(*b"staking ", &alice);
+        balances.set_lock(*b"vesting ", &alice, 0, WithdrawReasons::All).unwrap();
+        assert!(balances.locks(&alice).is_empty());
+        assert_eq!(balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 30), Ok(()));
+        assert_eq!(balances.free_balance(&alice), 0);
+    }
+
+    #[test]
+    fn existential_deposit_reaps_accounts() {
+        use crate::support::{ExistenceRequirement, LockableCurrency, WithdrawReasons};
+        use super::Event;
+
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        let system = crate::system::Pallet::<TestConfig>::new(balances.storage.clone());
+        balances.force_set_balance(Origin::Root, alice.clone(), 100).unwrap();
+        assert_eq!(system.providers(&alice), 1);
+        balances.take_events();
+
+        // An account cannot be created with less than the existential deposit.
+        assert_eq!(
+            balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 5),
+            Err(super::Error::ExistentialDeposit.into())
+        );
+        assert_eq!(balances.free_balance(&alice), 100);
+
+        // Unless asked to keep it alive, an account left with dust is reaped and the dust burned.
+        assert_eq!(
+            balances.withdraw(&alice, 95, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive),
+            Err(super::Error::KeepAlive.into())
+        );
+        assert_eq!(balances.transfer(Origin::Signed(alice.clone()), bob.clone(), 95), Ok(()));
+        assert_eq!(balances.free_balance(&alice), 0);
+        assert_eq!(system.providers(&alice), 0);
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                Event::DustLost { account: alice.clone(), amount: 5 },
+                Event::Transfer { from: alice.clone(), to: bob.clone(), amount: 95 },
+            ]
+        );
+
+        // The dust was burned.
+        assert_eq!(balances.total_issuance(), 95);
+
+        // Locks keep an account alive, and cannot be set on one which does not exist.
+        assert_eq!(
+            balances.set_lock(*b"staking ", &"charlie".to_string(), 10, WithdrawReasons::All),
+            Err(DispatchError::NoProviders)
+        );
+        balances.set_lock(*b"staking ", &bob, 10, WithdrawReasons::Fee).unwrap();
+        assert_eq!(system.consumers(&bob), 1);
+        assert_eq!(
+            balances.transfer(Origin::Signed(bob.clone()), alice.clone(), 90),
+            Err(super::Error::KeepAlive.into())
+        );
+        balances.remove_lock(*b"staking ", &bob);
+        assert_eq!(system.consumers(&bob), 0);
+        balances.force_set_balance(Origin::Root, bob.clone(), 0).unwrap();
+        assert_eq!(system.providers(&bob), 0);
+    }
+
+    #[test]
+    fn mint_and_burn() {
+        use crate::support::{ExistenceRequirement, Hooks, WithdrawReasons};
+
+        let alice = "alice".to_string();
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        let config = super::GenesisConfig::<TestConfig> { balances: vec![(alice.clone(), 100)], ..Default::default() };
+        balances.build_genesis(&config).unwrap();
+        assert_eq!(balances.total_issuance(), 100);
+
+        // The total issuance changes along with the balance.
+        assert_eq!(balances.mint_into(&alice, 50), Ok(()));
+        assert_eq!((balances.free_balance(&alice), balances.total_issuance()), (150, 150));
+        assert_eq!(balances.burn_from(&alice, 30, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive), Ok(()));
+        assert_eq!((balances.free_balance(&alice), balances.total_issuance()), (120, 120));
+        assert_eq!(balances.try_state(), Ok(()));
+
+        // Both are rolled back together, like the changes of a call which fails.
+        let storage = balances.storage.clone();
+        let result = storage.with_transaction(|| {
+            balances.mint_into(&alice, 50)?;
+            balances.burn_from(&alice, 10, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive)?;
+            Err::<(), _>(DispatchError::Other("the call failed"))
+        });
+        assert_eq!(result, Err(DispatchError::Other("the call failed")));
+        assert_eq!((balances.free_balance(&alice), balances.total_issuance()), (120, 120));
+        assert_eq!(balances.try_state(), Ok(()));
+
+        assert_eq!(balances.mint_into(&alice, u128::MAX), Err(DispatchError::Arithmetic(ArithmeticError::Overflow)));
+        assert_eq!(balances.mint_into(&"bob".to_string(), 5), Err(super::Error::ExistentialDeposit.into()));
+        assert_eq!(balances.total_issuance(), 120);
+
+        // Root mints or burns the difference when setting a balance.
+        balances.force_set_balance(Origin::Root, alice.clone(), 200).unwrap();
+        assert_eq!(balances.total_issuance(), 200);
+        balances.force_set_balance(Origin::Root, alice.clone(), 5).unwrap();
+        assert_eq!(balances.total_issuance(), 0);
+        assert_eq!(balances.try_state(), Ok(()));
+    }
+
+    #[test]
+    fn transfer_variants() {
+        use crate::support::{LockableCurrency, WithdrawReasons};
+        use super::Event;
+
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+        let charlie = "charlie".to_string();
+        let mut balances = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+        let config = super::GenesisConfig::<TestConfig> {
+            balances: vec![(alice.clone(), 100), (bob.clone(), 100)],
+            ..Default::default()
+        };
+        balances.build_genesis(&config).unwrap();
+
+        // Keeping the caller alive leaves it the existential deposit.
+        assert_eq!(
+            balances.transfer_keep_alive(Origin::Signed(alice.clone()), bob.clone(), 95),
+            Err(super::Error::KeepAlive.into())
+        );
+        assert_eq!(balances.transfer_keep_alive(Origin::Signed(alice.clone()), bob.clone(), 90), Ok(()));
+        assert_eq!(
+            balances.transfer_all(Origin::Signed(alice.clone()), bob.clone(), true),
+            Err(super::Error::NothingToTransfer.into())
+        );
+        assert_eq!(
+            balances.take_events(),
+            vec![Event::TransferKeepAlive { from: alice.clone(), to: bob.clone(), amount: 90 }]
+        );
+
+        // Locked funds are not transferred.
+        balances.set_lock(*b"staking ", &bob, 50, WithdrawReasons::Transfer).unwrap();
+        assert_eq!(balances.transfer_all(Origin::Signed(bob.clone()), charlie.clone(), false), Ok(()));
+        assert_eq!((balances.free_balance(&bob), balances.free_balance(&charlie)), (50, 140));
+        balances.remove_lock(*b"staking ", &bob);
+        assert_eq!(balances.transfer_all(Origin::Signed(bob.clone()), charlie.clone(), true), Ok(()));
+        assert_eq!(balances.free_balance(&bob), 10);
+        assert_eq!(balances.transfer_all(Origin::Signed(bob.clone()), charlie.clone(), false), Ok(()));
+        assert_eq!((balances.free_balance(&bob), balances.free_balance(&charlie)), (0, 190));
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                Event::TransferAll { from: bob.clone(), to: charlie.clone(), amount: 140, keep_alive: false },
+                Event::TransferAll { from: bob.clone(), to: charlie.clone(), amount: 40, keep_alive: true },
+                Event::TransferAll { from: bob.clone(), to: charlie.clone(), amount: 10, keep_alive: false },
+            ]
+        );
+
+        // A batch is applied in full, or not at all.
+        assert_eq!(
+            balances.transfer_many(Origin::Signed(charlie.clone()), vec![]),
+            Err(super::Error::NothingToTransfer.into())
+        );
+        assert_eq!(
+            balances.transfer_many(Origin::Signed(charlie.clone()), vec![(alice.clone(), 50), (bob.clone(), 5)]),
+            Err(super::Error::ExistentialDeposit.into())
+        );
+        assert_eq!(balances.free_balance(&charlie), 190);
+        assert!(balances.take_events().is_empty());
+        assert_eq!(
+            balances.transfer_many(
+                Origin::Signed(charlie.clone()),
+                vec![(alice.clone(), 50), (charlie.clone(), 30), (bob.clone(), 20)]
+            ),
+            Ok(())
+        );
+        assert_eq!(balances.free_balance(&charlie), 120);
+        assert_eq!(
+            balances.take_events(),
+            vec![
+                Event::Transfer { from: charlie.clone(), to: alice.clone(), amount: 50 },
+                Event::Transfer { from: charlie.clone(), to: bob.clone(), amount: 20 },
+                Event::BatchTransferred { from: charlie.clone(), count: 2, total: 70 },
+            ]
+        );
+    }
+}
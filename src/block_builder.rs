@@ -0,0 +1,193 @@
+use crate::support::{BlockError, DispatchResult, Hash};
+use crate::system::EventRecord;
+use crate::{types, Runtime, RuntimeEvent};
+
+/// The reason why an extrinsic was not added to the block being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushError {
+	/// The block already holds the maximum number of extrinsics.
+	LimitReached,
+	/// The extrinsic cannot be included in the block, for example because of its nonce.
+	Invalid(BlockError),
+}
+
+/// Builds the next block on top of the current state of a runtime, one extrinsic at a time.
+///
+/// Extrinsics are applied as they are pushed, so the outcome of every call is known right away.
+/// `finalize` returns the block with a header matching the resulting state. The builder never
+/// changes the state of the runtime itself: the block is then imported with `execute_block`, like
+/// a block received from any other node.
+pub struct BlockBuilder<'a> {
+	runtime: &'a mut Runtime,
+	parent_hash: Hash,
+	extrinsics: Vec<types::Extrinsic>,
+	limit: usize,
+	/// The events of the last imported block, which building the block clears, and which are
+	/// restored once the builder is dropped.
+	events: Vec<EventRecord<RuntimeEvent>>,
+}
+
+impl<'a> BlockBuilder<'a> {
+	/// Start building the block after the current block of `runtime`, holding at most `limit`
+	/// extrinsics. Fails if the genesis state of `runtime` is not built yet.
+	pub fn new(runtime: &'a mut Runtime, limit: usize) -> Result<Self, BlockError> {
+		if runtime.system.genesis_hash().is_none() {
+			return Err(BlockError::NoGenesis)
+		}
+		let events = runtime.system.events().to_vec();
+		runtime.system.storage().start_transaction();
+		let parent_hash = runtime.initialize_block();
+		Ok(Self { runtime, parent_hash, extrinsics: Vec::new(), limit, events })
+	}
+
+	/// The number of the block being built.
+	pub fn block_number(&self) -> types::BlockNumber {
+		self.runtime.system.block_number()
+	}
+
+	/// Apply `extrinsic` and add it to the block. Returns the result of dispatching its call, which
+	/// is included in the block whether it succeeds or not.
+	pub fn push(&mut self, extrinsic: types::Extrinsic) -> Result<DispatchResult, PushError> {
+		if self.extrinsics.len() >= self.limit {
+			return Err(PushError::LimitReached)
+		}
+		// An extrinsic which cannot be included may have made changes while it was checked.
+		let storage = self.runtime.system.storage().clone();
+		let res = storage
+			.with_transaction(|| self.runtime.apply_extrinsic(self.extrinsics.len() as u32, extrinsic.clone()))
+			.map_err(PushError::Invalid)?;
+		self.extrinsics.push(extrinsic);
+		Ok(res)
+	}
+
+	/// Finish the block, and discard the changes made while building it.
+	pub fn finalize(mut self) -> types::Block {
+		self.runtime.finalize_block();
+		let extrinsics = core::mem::take(&mut self.extrinsics);
+		let header = self.runtime.build_header(self.parent_hash, &extrinsics);
+		types::Block { header, extrinsics }
+	}
+}
+
+impl Drop for BlockBuilder<'_> {
+	fn drop(&mut self) {
+		self.runtime.system.storage().rollback_transaction();
+		self.runtime.reset_events();
+		self.runtime.system.set_events(core::mem::take(&mut self.events));
+	}
+}
+
+#[cfg(test)]
+impl Runtime {
+	/// Build the next block out of `extrinsics`. Fails if any of them cannot be included.
+	pub fn build_block(&mut self, extrinsics: Vec<types::Extrinsic>) -> Result<types::Block, BlockError> {
+		let mut builder = BlockBuilder::new(self, usize::MAX)?;
+		for extrinsic in extrinsics {
+			// A call which fails to dispatch does not make the block invalid.
+			let _ = builder.push(extrinsic).map_err(|error| match error {
+				PushError::Invalid(error) => error,
+				PushError::LimitReached => unreachable!("there is no limit"),
+			})?;
+		}
+		Ok(builder.finalize())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{BlockBuilder, PushError};
+	use crate::support::{BlockError, DispatchError, ModuleError};
+	use crate::{balances, crypto, signed_extrinsic, storage, types, Runtime, RuntimeCall};
+
+	fn transfer(to: &crypto::Pair, amount: types::Balance) -> RuntimeCall {
+		RuntimeCall::balances(balances::Call::transfer { to: to.public(), amount })
+	}
+
+	fn new_runtime(alice: &crypto::Pair) -> Runtime {
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		let mut genesis = crate::RuntimeGenesisConfig::default();
+		genesis.balances.balances = vec![(alice.public(), 100)];
+		runtime.build_genesis(&genesis).unwrap();
+		runtime
+	}
+
+	#[test]
+	fn built_blocks_can_be_imported() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut author = new_runtime(&alice);
+		let mut importer = new_runtime(&alice);
+		// There is no block to build on before the genesis state is built.
+		let mut unbuilt = Runtime::new(storage::Storage::in_memory());
+		assert!(matches!(BlockBuilder::new(&mut unbuilt, 3), Err(BlockError::NoGenesis)));
+
+		let extrinsics = [
+			signed_extrinsic(&author, &alice, 0, transfer(&bob, 30)),
+			signed_extrinsic(&author, &alice, 2, transfer(&bob, 30)),
+			signed_extrinsic(&author, &alice, 1, transfer(&bob, 300)),
+			signed_extrinsic(&author, &alice, 2, transfer(&bob, 30)),
+		];
+		let extra = signed_extrinsic(&author, &alice, 3, transfer(&bob, 0));
+		let mut builder = BlockBuilder::new(&mut author, 3).unwrap();
+		assert_eq!(builder.block_number(), 1);
+		let results =
+			extrinsics.iter().map(|extrinsic| builder.push(extrinsic.clone())).collect::<Vec<_>>();
+		assert_eq!(
+			results,
+			vec![
+				Ok(Ok(())),
+				Err(PushError::Invalid(BlockError::FutureNonce { extrinsic_index: 1 })),
+				Ok(Err(DispatchError::Module(ModuleError {
+					index: 1,
+					error: 0,
+					message: "InsufficientBalance",
+				}))),
+				Ok(Ok(())),
+			]
+		);
+		assert_eq!(builder.push(extra), Err(PushError::LimitReached));
+		let block = builder.finalize();
+		assert_eq!(block.extrinsics.len(), 3);
+		assert_eq!(author.system.block_number(), 0);
+		assert_eq!(author.balances.free_balance(&bob.public()), 0);
+
+		assert_eq!(unbuilt.execute_block(block.clone()), Err(BlockError::NoGenesis));
+		assert_eq!(importer.execute_block(block.clone()), Ok(()));
+		assert_eq!(importer.balances.free_balance(&bob.public()), 60);
+		assert_eq!(author.execute_block(block), Ok(()));
+		assert_eq!(importer.state_root(), author.state_root());
+		assert_eq!(importer.system.block_hash(&1), author.system.block_hash(&1));
+		assert!(matches!(
+			importer.system.events()[4].event,
+			crate::RuntimeEvent::system(crate::system::Event::ExtrinsicFailed {
+				error: DispatchError::Module(_)
+			})
+		));
+	}
+
+	#[test]
+	fn builders_leave_no_trace() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30))]).unwrap();
+		runtime.execute_block(block).unwrap();
+		let state_root = runtime.state_root();
+		// Runtime events are compared by how they are printed, since they cannot be compared.
+		let events = format!("{:?}", runtime.system.events());
+		assert!(!runtime.system.events().is_empty());
+
+		let extrinsic = signed_extrinsic(&runtime, &alice, 1, transfer(&bob, 30));
+		let mut builder = BlockBuilder::new(&mut runtime, 10).unwrap();
+		assert_eq!(builder.push(extrinsic.clone()), Ok(Ok(())));
+		builder.finalize();
+		// A builder which is abandoned before the block is finished leaves no trace either.
+		let mut builder = BlockBuilder::new(&mut runtime, 10).unwrap();
+		assert_eq!(builder.push(extrinsic), Ok(Ok(())));
+		drop(builder);
+
+		assert_eq!(runtime.state_root(), state_root);
+		assert_eq!(runtime.system.block_number(), 1);
+		assert_eq!(format!("{:?}", runtime.system.events()), events);
+	}
+}
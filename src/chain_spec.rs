@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use crate::RuntimeGenesisConfig;
+
+/// The development chain spec, used when no chain spec file is given.
+pub const DEVELOPMENT: &str = include_str!("../chain_spec.json");
+
+/// The description of a chain, which holds everything needed to build its genesis state.
+///
+/// Chain specs are written as JSON. Accounts are given either as a hex public key, or as `//`
+/// followed by the seed of a development account, like "//alice".
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+	/// The human readable name of the chain.
+	pub name: String,
+	/// The genesis configuration of every pallet.
+	pub genesis: RuntimeGenesisConfig,
+}
+
+/// The reason why a chain spec could not be loaded.
+#[derive(Debug)]
+pub enum Error {
+	/// The file could not be read.
+	Io(std::io::Error),
+	/// The file is not a valid chain spec.
+	Json(serde_json::Error),
+}
+
+impl core::fmt::Display for Error {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Error::Io(error) => write!(f, "failed to read the chain spec: {}", error),
+			Error::Json(error) => write!(f, "invalid chain spec: {}", error),
+		}
+	}
+}
+
+impl ChainSpec {
+	pub fn from_json(json: &str) -> Result<Self, Error> {
+		serde_json::from_str(json).map_err(Error::Json)
+	}
+
+	/// Load the chain spec stored in the file at `path`.
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+		Self::from_json(&std::fs::read_to_string(path).map_err(Error::Io)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ChainSpec, Error, DEVELOPMENT};
+	use crate::crypto::Pair;
+	use crate::support::WithdrawReasons;
+
+	#[test]
+	fn parse_chain_spec() {
+		let spec = ChainSpec::from_json(DEVELOPMENT).unwrap();
+		assert_eq!(spec.name, "Development");
+		assert_eq!(spec.genesis.balances.balances[0], (Pair::from_seed("alice").public(), 1_000_000));
+		assert_eq!(spec.genesis.transaction_payment.treasury, Some(Pair::from_seed("treasury").public()));
+
+		// Pallets which are left out use their default configuration.
+		let spec = ChainSpec::from_json(r#"{ "name": "Empty", "genesis": {} }"#).unwrap();
+		assert!(spec.genesis.balances.balances.is_empty());
+
+		let json = r#"{ "name": "Locked", "genesis": { "balances": {
+			"balances": [["//alice", 100]], "locks": [["//alice", 50, "Transfer"]]
+		} } }"#;
+		let spec = ChainSpec::from_json(json).unwrap();
+		assert_eq!(spec.genesis.balances.locks, vec![(Pair::from_seed("alice").public(), 50, WithdrawReasons::Transfer)]);
+
+		let invalid = [
+			r#"{ "name": "Typo", "genesis": { "balance": { "balances": [] } } }"#,
+			r#"{ "name": "Bad account", "genesis": { "balances": { "balances": [["alice", 1]] } } }"#,
+			r#"{ "name": "Negative", "genesis": { "balances": { "balances": [["//alice", -1]] } } }"#,
+		];
+		for json in invalid {
+			assert!(matches!(ChainSpec::from_json(json), Err(Error::Json(_))), "{}", json);
+		}
+	}
+}
@@ -0,0 +1,391 @@
+//! A compact and deterministic binary codec, modelled after SCALE.
+//!
+//! - Fixed width integers are encoded as little endian.
+//! - `Compact` integers use between 1 and 17 bytes, depending on their value.
+//! - Vectors and strings are prefixed with their length, as a `Compact<u32>`.
+//! - Enums are encoded as the index of the variant, followed by its fields.
+//!
+//! The same value is always encoded to the same bytes, so encoded values can be hashed and signed.
+
+/// The reason why some bytes could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The input ended before the value was fully decoded.
+	UnexpectedEnd,
+	/// The input contained bytes after the value was fully decoded.
+	TrailingBytes,
+	/// The index of an enum variant is unknown.
+	InvalidVariant,
+	/// A compact integer was not encoded in its shortest form, or does not fit its type.
+	InvalidCompact,
+	/// A string was not valid UTF-8.
+	InvalidUtf8,
+	/// Values which contain values of the same kind, like calls holding other calls, were nested
+	/// deeper than allowed.
+	TooDeep,
+}
+
+/// A type which can be encoded into bytes.
+pub trait Encode {
+	/// Append the encoding of `self` to `dest`.
+	fn encode_to(&self, dest: &mut Vec<u8>);
+
+	/// Return the encoding of `self`.
+	fn encode(&self) -> Vec<u8> {
+		let mut dest = Vec::new();
+		self.encode_to(&mut dest);
+		dest
+	}
+}
+
+/// A type which can be decoded from bytes.
+pub trait Decode: Sized {
+	/// Decode a value from the start of `input`, advancing `input` past the decoded bytes.
+	fn decode(input: &mut &[u8]) -> Result<Self, Error>;
+}
+
+/// Decode a value, making sure that all of `bytes` are used.
+pub fn decode_all<T: Decode>(mut bytes: &[u8]) -> Result<T, Error> {
+	let value = T::decode(&mut bytes)?;
+	if !bytes.is_empty() {
+		return Err(Error::TrailingBytes)
+	}
+	Ok(value)
+}
+
+thread_local! {
+	/// The number of values being decoded by `decode_with_depth_limit` on this thread.
+	static DEPTH: core::cell::Cell<u32> = const { core::cell::Cell::new(0) };
+}
+
+/// Decode a value with `f`, which may in turn decode values of the same kind through this function.
+/// Fails with `Error::TooDeep` once more than `max_depth` of them are nested, rather than letting a
+/// crafted input overflow the stack.
+pub fn decode_with_depth_limit<T>(
+	input: &mut &[u8],
+	max_depth: u32,
+	f: impl FnOnce(&mut &[u8]) -> Result<T, Error>,
+) -> Result<T, Error> {
+	let depth = DEPTH.get();
+	if depth >= max_depth {
+		return Err(Error::TooDeep)
+	}
+	DEPTH.set(depth + 1);
+	let result = f(input);
+	DEPTH.set(depth);
+	result
+}
+
+/// Take the next `len` bytes from `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+	if input.len() < len {
+		return Err(Error::UnexpectedEnd)
+	}
+	let (bytes, rest) = input.split_at(len);
+	*input = rest;
+	Ok(bytes)
+}
+
+macro_rules! impl_fixed_width {
+	( $( $t:ty ),* ) => { $(
+		impl Encode for $t {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				dest.extend_from_slice(&self.to_le_bytes());
+			}
+		}
+
+		impl Decode for $t {
+			fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+				let bytes = take(input, core::mem::size_of::<$t>())?;
+				Ok(<$t>::from_le_bytes(bytes.try_into().expect("took the size of the type")))
+			}
+		}
+	)* };
+}
+
+impl_fixed_width!(u8, u16, u32, u64, u128);
+
+impl Encode for bool {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		dest.push(*self as u8);
+	}
+}
+
+impl Decode for bool {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(Error::InvalidVariant),
+		}
+	}
+}
+
+/// An integer which is encoded using as few bytes as possible.
+///
+/// The two lowest bits of the first byte select the mode:
+/// - `0b00`: a single byte, for values up to `2^6 - 1`.
+/// - `0b01`: two bytes, for values up to `2^14 - 1`.
+/// - `0b10`: four bytes, for values up to `2^30 - 1`.
+/// - `0b11`: the upper six bits hold the number of following bytes minus four, and the value is
+///   stored in those bytes as little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compact<T>(pub T);
+
+fn encode_compact(value: u128, dest: &mut Vec<u8>) {
+	match value {
+		0..=0x3f => dest.push((value as u8) << 2),
+		0x40..=0x3fff => dest.extend_from_slice(&(((value as u16) << 2) | 0b01).to_le_bytes()),
+		0x4000..=0x3fff_ffff => dest.extend_from_slice(&(((value as u32) << 2) | 0b10).to_le_bytes()),
+		_ => {
+			let bytes = value.to_le_bytes();
+			let len = bytes.iter().rposition(|b| *b != 0).expect("value is not zero") + 1;
+			dest.push((((len - 4) as u8) << 2) | 0b11);
+			dest.extend_from_slice(&bytes[..len]);
+		},
+	}
+}
+
+fn decode_compact(input: &mut &[u8]) -> Result<u128, Error> {
+	let first = u8::decode(input)?;
+	let (value, min) = match first & 0b11 {
+		0b00 => return Ok(u128::from(first >> 2)),
+		0b01 => {
+			let value = u16::from_le_bytes([first, u8::decode(input)?]) >> 2;
+			(u128::from(value), 0x40)
+		},
+		0b10 => {
+			let rest = take(input, 3)?;
+			let value = u32::from_le_bytes([first, rest[0], rest[1], rest[2]]) >> 2;
+			(u128::from(value), 0x4000)
+		},
+		_ => {
+			let len = usize::from(first >> 2) + 4;
+			if len > 16 {
+				return Err(Error::InvalidCompact)
+			}
+			let mut bytes = [0u8; 16];
+			bytes[..len].copy_from_slice(take(input, len)?);
+			(u128::from_le_bytes(bytes), if len == 4 { 0x4000_0000 } else { 1u128 << ((len - 1) * 8) })
+		},
+	};
+	// Every value has exactly one encoding, the shortest one.
+	if value < min {
+		return Err(Error::InvalidCompact)
+	}
+	Ok(value)
+}
+
+macro_rules! impl_compact {
+	( $( $t:ty ),* ) => { $(
+		impl Encode for Compact<$t> {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				encode_compact(u128::from(self.0), dest);
+			}
+		}
+
+		impl Decode for Compact<$t> {
+			fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+				let value = decode_compact(input)?;
+				<$t>::try_from(value).map(Compact).map_err(|_| Error::InvalidCompact)
+			}
+		}
+	)* };
+}
+
+impl_compact!(u32, u64, u128);
+
+/// Decode the length prefix of a vector or string.
+fn decode_len(input: &mut &[u8]) -> Result<usize, Error> {
+	let len = Compact::<u32>::decode(input)?.0 as usize;
+	// Every item takes at least one byte, so this protects against huge allocations.
+	if len > input.len() {
+		return Err(Error::UnexpectedEnd)
+	}
+	Ok(len)
+}
+
+impl<T: Encode> Encode for [T] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		Compact(self.len() as u32).encode_to(dest);
+		for item in self {
+			item.encode_to(dest);
+		}
+	}
+}
+
+impl<T: Encode> Encode for Vec<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_slice().encode_to(dest);
+	}
+}
+
+impl<T: Decode> Decode for Vec<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		(0..len).map(|_| T::decode(input)).collect()
+	}
+}
+
+impl Encode for str {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		Compact(self.len() as u32).encode_to(dest);
+		dest.extend_from_slice(self.as_bytes());
+	}
+}
+
+impl Encode for String {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_str().encode_to(dest);
+	}
+}
+
+impl Decode for String {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		let len = decode_len(input)?;
+		let bytes = take(input, len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidUtf8)
+	}
+}
+
+impl<const N: usize> Encode for [u8; N] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		dest.extend_from_slice(self);
+	}
+}
+
+impl<const N: usize> Decode for [u8; N] {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(take(input, N)?.try_into().expect("took N bytes"))
+	}
+}
+
+impl<T: Encode> Encode for Option<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			None => dest.push(0),
+			Some(value) => {
+				dest.push(1);
+				value.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl<T: Decode> Decode for Option<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		match u8::decode(input)? {
+			0 => Ok(None),
+			1 => Ok(Some(T::decode(input)?)),
+			_ => Err(Error::InvalidVariant),
+		}
+	}
+}
+
+// A box is encoded as the value it holds, which lets calls hold other calls.
+impl<T: Encode + ?Sized> Encode for Box<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest);
+	}
+}
+
+impl<T: Decode> Decode for Box<T> {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		T::decode(input).map(Box::new)
+	}
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest);
+	}
+}
+
+macro_rules! impl_tuple {
+	( $( $name:ident ),* ) => {
+		impl<$( $name: Encode ),*> Encode for ( $( $name, )* ) {
+			#[allow(non_snake_case)]
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				let ( $( $name, )* ) = self;
+				$( $name.encode_to(dest); )*
+			}
+		}
+
+		impl<$( $name: Decode ),*> Decode for ( $( $name, )* ) {
+			fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+				Ok(( $( $name::decode(input)?, )* ))
+			}
+		}
+	};
+}
+
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+
+#[cfg(test)]
+mod tests {
+	use super::{decode_all, Compact, Decode, Encode, Error};
+
+	fn round_trip<T: Encode + Decode + PartialEq + core::fmt::Debug>(value: T) {
+		assert_eq!(decode_all::<T>(&value.encode()), Ok(value));
+	}
+
+	#[test]
+	fn compact_integers() {
+		let cases: [(u128, &[u8]); 8] = [
+			(0, &[0x00]),
+			(63, &[0xfc]),
+			(64, &[0x01, 0x01]),
+			(16383, &[0xfd, 0xff]),
+			(16384, &[0x02, 0x00, 0x01, 0x00]),
+			((1 << 30) - 1, &[0xfe, 0xff, 0xff, 0xff]),
+			(1 << 30, &[0x03, 0x00, 0x00, 0x00, 0x40]),
+			(u64::MAX as u128, &[0x13, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+		];
+		for (value, bytes) in cases {
+			assert_eq!(Compact(value).encode(), bytes);
+			assert_eq!(decode_all::<Compact<u128>>(bytes), Ok(Compact(value)));
+		}
+		round_trip(Compact(u128::MAX));
+		round_trip(Compact(u32::MAX));
+
+		// Values have a single valid encoding, and must fit in the decoded type.
+		assert_eq!(decode_all::<Compact<u32>>(&[0x01, 0x00]), Err(Error::InvalidCompact));
+		assert_eq!(Compact::<u32>::decode(&mut &Compact(u64::MAX).encode()[..]), Err(Error::InvalidCompact));
+	}
+
+	#[test]
+	fn round_trips() {
+		round_trip(42u8);
+		round_trip(u32::MAX);
+		round_trip(u128::MAX);
+		round_trip(true);
+		round_trip(String::from("Hello, world!"));
+		round_trip(vec![1u32, 2, 3]);
+		round_trip([7u8; 32]);
+		round_trip(Some(5u64));
+		round_trip(None::<u64>);
+		round_trip(Box::new(7u16));
+		round_trip((1u8, String::from("a"), vec![true, false]));
+	}
+
+	#[test]
+	fn known_encodings() {
+		assert_eq!(1u32.encode(), vec![1, 0, 0, 0]);
+		assert_eq!("abc".encode(), vec![12, b'a', b'b', b'c']);
+		assert_eq!(vec![1u16, 2].encode(), vec![8, 1, 0, 2, 0]);
+		assert_eq!(Some(1u8).encode(), vec![1, 1]);
+	}
+
+	#[test]
+	fn invalid_input() {
+		assert_eq!(decode_all::<u32>(&[1, 0, 0]), Err(Error::UnexpectedEnd));
+		assert_eq!(decode_all::<u8>(&[1, 0]), Err(Error::TrailingBytes));
+		assert_eq!(decode_all::<bool>(&[2]), Err(Error::InvalidVariant));
+		assert_eq!(decode_all::<String>(&[8, b'a']), Err(Error::UnexpectedEnd));
+		assert_eq!(decode_all::<String>(&[4, 0xc3]), Err(Error::InvalidUtf8));
+		assert_eq!(decode_all::<Vec<u8>>(&[0xfd, 0xff, 1]), Err(Error::UnexpectedEnd));
+	}
+}
@@ -0,0 +1,133 @@
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use crate::codec::{Decode, Encode, Error};
+
+/// An ed25519 public key. In our runtime, the public key of an account is also its account id.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Public(pub [u8; 32]);
+
+/// Public keys are displayed as hex, which is much easier to read than a list of bytes.
+impl core::fmt::Debug for Public {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		write!(f, "0x")?;
+		for byte in self.0 {
+			write!(f, "{:02x}", byte)?;
+		}
+		Ok(())
+	}
+}
+
+/// Parse an account from a chain spec: either the public key as hex, optionally prefixed by `0x`,
+/// or a development account as `//` followed by its seed, like "//alice".
+impl core::str::FromStr for Public {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some(seed) = s.strip_prefix("//") {
+			return Ok(Pair::from_seed(seed).public())
+		}
+		let hex = s.strip_prefix("0x").unwrap_or(s);
+		if hex.len() != 64 || !hex.is_ascii() {
+			return Err("expected a 32 byte hex public key")
+		}
+		let mut public = [0u8; 32];
+		for (i, byte) in public.iter_mut().enumerate() {
+			*byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+				.map_err(|_| "expected a 32 byte hex public key")?;
+		}
+		Ok(Self(public))
+	}
+}
+
+impl<'de> serde::Deserialize<'de> for Public {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		s.parse().map_err(serde::de::Error::custom)
+	}
+}
+
+impl Encode for Public {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for Public {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(Self(Decode::decode(input)?))
+	}
+}
+
+/// An ed25519 signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature(pub [u8; 64]);
+
+impl Encode for Signature {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for Signature {
+	fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+		Ok(Self(Decode::decode(input)?))
+	}
+}
+
+impl crate::support::Verify for Signature {
+	type Signer = Public;
+
+	fn verify(&self, message: &[u8], signer: &Self::Signer) -> bool {
+		let Ok(public) = VerifyingKey::from_bytes(&signer.0) else { return false };
+		let signature = ed25519_dalek::Signature::from_bytes(&self.0);
+		public.verify(message, &signature).is_ok()
+	}
+}
+
+/// An ed25519 key pair, used to sign extrinsics.
+pub struct Pair(SigningKey);
+
+impl Pair {
+	/// Derive a key pair from a human readable seed, like "alice".
+	///
+	/// This is only meant for development: anyone who knows the seed can sign with the key.
+	pub fn from_seed(seed: &str) -> Self {
+		Self(SigningKey::from_bytes(&crate::support::blake2_256(seed.as_bytes())))
+	}
+
+	pub fn public(&self) -> Public {
+		Public(self.0.verifying_key().to_bytes())
+	}
+
+	pub fn sign(&self, message: &[u8]) -> Signature {
+		Signature(self.0.sign(message).to_bytes())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Pair;
+	use crate::support::Verify;
+
+	#[test]
+	fn sign_and_verify() {
+		let alice = Pair::from_seed("alice");
+		let bob = Pair::from_seed("bob");
+		assert_ne!(alice.public(), bob.public());
+
+		let signature = alice.sign(b"hello");
+		assert!(signature.verify(b"hello", &alice.public()));
+		assert!(!signature.verify(b"goodbye", &alice.public()));
+		assert!(!signature.verify(b"hello", &bob.public()));
+	}
+
+	#[test]
+	fn parse_public() {
+		let alice = Pair::from_seed("alice").public();
+		let hex = format!("{:?}", alice);
+		assert_eq!("//alice".parse(), Ok(alice));
+		assert_eq!(hex.parse(), Ok(alice));
+		assert_eq!(hex[2..].parse(), Ok(alice));
+		assert!(hex[..10].parse::<super::Public>().is_err());
+		assert!("0x".repeat(32).parse::<super::Public>().is_err());
+	}
+}
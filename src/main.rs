@@ -1,19 +1,32 @@
 mod balances;
+mod block_builder;
+mod chain_spec;
+mod codec;
+mod crypto;
+mod metadata;
 mod system;
+mod storage;
+mod sudo;
 mod support;
 mod proof_of_existence;
+mod transaction_pool;
+mod transaction_payment;
+#[cfg(test)]
+mod test_pallet;
 
 use crate::support::Dispatch;
 
 mod types {
-	pub type AccountId = String;
+	pub type AccountId = crate::crypto::Public;
 	pub type Balance = u128;
     pub type Nonce = u32;
     pub type BlockNumber = u32;
-    pub type Extrinsic = crate::support::Extrinsic<AccountId, crate::RuntimeCall>;
+    pub type Signature = crate::crypto::Signature;
+    pub type Extrinsic =
+        crate::support::Extrinsic<AccountId, crate::RuntimeCall, Nonce, BlockNumber, Balance, Signature>;
 	pub type Header = crate::support::Header<BlockNumber>;
 	pub type Block = crate::support::Block<Header, Extrinsic>;
-	pub type Content = &'static str;
+	pub type Content = String;
 }
 
 
@@ -21,77 +34,897 @@ mod types {
 #[macros::runtime]
 pub struct Runtime {
 	system: system::Pallet<Self>,
+	#[pallet_index(1)]
 	balances: balances::Pallet<Self>,
+	#[pallet_index(2)]
+	#[uses(balances)]
 	proof_of_existence: proof_of_existence::Pallet<Self>,
+	#[pallet_index(3)]
+	transaction_payment: transaction_payment::Pallet<Self>,
+	/// A second currency, which is not used to pay fees.
+	#[pallet_index(4)]
+	credits: balances::Pallet<Self, support::Instance1>,
+	#[pallet_index(5)]
+	sudo: sudo::Pallet<Self>,
 }
 
 impl system::Config for Runtime {
 	type AccountId = types::AccountId;
 	type BlockNumber = types::BlockNumber;
 	type Nonce = types::Nonce;
+	type Signature = types::Signature;
+	type RuntimeOrigin = support::Origin<types::AccountId>;
+	type RuntimeEvent = RuntimeEvent;
+	type PalletInfo = Self;
+	const BLOCK_WEIGHT_LIMIT: support::Weight = 1_000_000;
 }
 
 impl balances::Config for Runtime {
     type Balance = types::Balance;
+    const EXISTENTIAL_DEPOSIT: types::Balance = 1;
+}
+
+impl balances::Config<support::Instance1> for Runtime {
+	type Balance = types::Balance;
+	const EXISTENTIAL_DEPOSIT: types::Balance = 1;
 }
 
 impl proof_of_existence::Config for Runtime {
 	type Content = types::Content;
+	type Currency = balances::Pallet<Self>;
+	const CLAIM_DEPOSIT: types::Balance = 10;
+}
+
+impl transaction_payment::Config for Runtime {}
+
+impl sudo::Config for Runtime {
+	type RuntimeCall = RuntimeCall;
+}
+
+// Fees are paid out of the balances pallet.
+impl support::ChargeTransaction for Runtime {
+	type AccountId = types::AccountId;
+	type Balance = types::Balance;
+
+	fn withdraw_fee(
+		&mut self,
+		who: &types::AccountId,
+		info: &support::DispatchInfo,
+		len: u32,
+		tip: types::Balance,
+	) -> Option<types::Balance> {
+		self.transaction_payment.withdraw_fee(&mut self.balances, who, info, len, tip)
+	}
+
+	fn can_pay_fee(&self, who: &types::AccountId, info: &support::DispatchInfo, len: u32, tip: types::Balance) -> bool {
+		self.transaction_payment.can_pay_fee(&self.balances, who, info, len, tip)
+	}
+
+	fn settle_fee(&mut self, who: &types::AccountId, settlement: &support::FeeSettlement<types::Balance>) {
+		self.transaction_payment.settle_fee(&mut self.balances, who, settlement)
+	}
+}
+
+/// The maximum number of extrinsics in a block.
+const MAX_BLOCK_EXTRINSICS: usize = 100;
+
+/// Build an extrinsic making `call`, signed by `pair` with the given `nonce`.
+#[cfg(test)]
+fn signed_extrinsic(
+	runtime: &Runtime,
+	pair: &crypto::Pair,
+	nonce: types::Nonce,
+	call: RuntimeCall,
+) -> types::Extrinsic {
+	tipped_extrinsic(runtime, pair, nonce, 0, call)
+}
+
+/// Build an extrinsic making `call`, signed by `pair` with the given `nonce`, which pays `tip` on
+/// top of its fee. Its checkpoint is the last block imported by `runtime`.
+fn tipped_extrinsic(
+	runtime: &Runtime,
+	pair: &crypto::Pair,
+	nonce: types::Nonce,
+	tip: types::Balance,
+	call: RuntimeCall,
+) -> types::Extrinsic {
+	let checkpoint = runtime.system.block_number();
+	let checkpoint_hash = runtime.system.block_hash(&checkpoint).expect("the last block was imported");
+	let genesis_hash = runtime.system.genesis_hash().expect("the genesis state was built");
+	let payload = support::signing_payload(&call, &nonce, &tip, &genesis_hash, &checkpoint_hash);
+	support::Extrinsic {
+		signature: Some(support::SignaturePayload {
+			caller: pair.public(),
+			nonce,
+			checkpoint,
+			tip,
+			signature: pair.sign(&payload),
+		}),
+		call,
+	}
 }
 
 fn main() {
-	let mut runtime = Runtime::new();
-    
-    runtime.balances.set_balance(&"alice".to_string(), 100);
-
-	let block_1 = types::Block {
-		header: types::Header { block_number: 1 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: "alice".to_string(),
-				call: RuntimeCall::balances(balances::Call::transfer { to: "bob".to_string(), amount: 30 }),
-			},
-			support::Extrinsic {
-				caller: "alice".to_string(),
-				call: RuntimeCall::balances(balances::Call::transfer { to: "charlie".to_string(), amount: 20 }),
+	// `--chain <file>` is the chain spec to build the genesis state from, and `--state <file>` the
+	// file which holds the state, so the chain continues from where it stopped the next time it is
+	// run. By default, the development chain is run in memory. `--metadata` prints the metadata of
+	// the runtime as JSON instead.
+	let mut chain = None;
+	let mut state = None;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		if arg == "--metadata" {
+			let metadata = serde_json::to_string_pretty(&Runtime::metadata()).expect("metadata is valid JSON");
+			println!("{}", metadata);
+			return;
+		}
+		match (arg.as_str(), args.next()) {
+			("--chain", Some(path)) => chain = Some(path),
+			("--state", Some(path)) => state = Some(path),
+			_ => {
+				eprintln!("usage: {} [--chain <file>] [--state <file>] [--metadata]", env!("CARGO_PKG_NAME"));
+				std::process::exit(1);
 			},
-		]
-	};
+		}
+	}
 
-	let block_2 = types::Block {
-		header: types::Header { block_number: 2 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: "bob".to_string(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "Hello, world!" }),
-			},
-			support::Extrinsic {
-				caller: "charlie".to_string(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "Hello, world!" }),
-			},
-		]
+	let spec = match chain {
+		Some(path) => chain_spec::ChainSpec::load(path),
+		None => chain_spec::ChainSpec::from_json(chain_spec::DEVELOPMENT),
+	}
+	.unwrap_or_else(|error| {
+		eprintln!("{}", error);
+		std::process::exit(1);
+	});
+	let storage = match state {
+		Some(path) => storage::Storage::new(storage::FileBackend::open(path).expect("failed to open the state")),
+		None => storage::Storage::in_memory(),
 	};
+	let mut runtime = Runtime::new(storage);
+	match runtime.build_genesis(&spec.genesis) {
+		Ok(()) => println!("Built the genesis state of {}", spec.name),
+		Err(support::GenesisError::AlreadyBuilt) => println!("Continuing {}", spec.name),
+		Err(error) => {
+			eprintln!("invalid genesis config: {:?}", error);
+			std::process::exit(1);
+		},
+	}
 
-	let block_3 = types::Block {
-		header: types::Header { block_number: 3 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: "bob".to_string(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: "Hello, world!" }),
-			},
-			support::Extrinsic {
-				caller: "bob".to_string(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
-					claim: "Hello, world!",
-				}),
-			},
-		]
-	};
+	let alice = crypto::Pair::from_seed("alice");
+	let bob = crypto::Pair::from_seed("bob");
+	let charlie = crypto::Pair::from_seed("charlie");
+
+	let claim = || "Hello, world!".to_string();
+	// The extrinsics submitted before each block, with their nonce relative to the nonce of their
+	// caller and their tip. Alice's second transfer arrives before her first, so it waits in the
+	// future queue until the first one arrives.
+	let rounds = [
+		vec![
+			(&alice, 1, 0, RuntimeCall::balances(balances::Call::transfer { to: charlie.public(), amount: 20 })),
+			(&alice, 0, 0, RuntimeCall::balances(balances::Call::transfer { to: bob.public(), amount: 30 })),
+		],
+		vec![
+			(&bob, 0, 0, RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: claim() })),
+			(&charlie, 0, 500, RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: claim() })),
+		],
+		vec![
+			(&bob, 0, 0, RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: claim() })),
+			(&bob, 1, 0, RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: claim() })),
+			// Alice is the sudo key of the development chain, so she can move the funds of others.
+			(&alice, 0, 0, RuntimeCall::sudo(sudo::Call::sudo {
+				call: Box::new(RuntimeCall::balances(balances::Call::force_transfer {
+					from: charlie.public(),
+					to: bob.public(),
+					amount: 10,
+				})),
+			})),
+		],
+	];
+
+	let mut pool = transaction_pool::TransactionPool::new();
+	for round in rounds {
+		for (pair, offset, tip, call) in round {
+			let nonce = runtime.system.nonce(&pair.public()) + offset;
+			let extrinsic = tipped_extrinsic(&runtime, pair, nonce, tip, call);
+			// Extrinsics are shared with other nodes in their encoded form.
+			if let Err(error) = pool.submit(&runtime, &codec::Encode::encode(&extrinsic)) {
+				println!("Rejected extrinsic: {:?}", error);
+			}
+		}
+		let (ready, future) = pool.status();
+		println!("Transaction pool: {} ready, {} future", ready, future);
+
+		let mut builder = block_builder::BlockBuilder::new(&mut runtime, MAX_BLOCK_EXTRINSICS)
+			.expect("the genesis state was built");
+		let mut invalid = Vec::new();
+		for extrinsic in pool.best(MAX_BLOCK_EXTRINSICS) {
+			match builder.push(extrinsic.clone()) {
+				Ok(_) => {},
+				Err(block_builder::PushError::LimitReached) => break,
+				Err(block_builder::PushError::Invalid(error)) => {
+					println!("Skipped extrinsic in block {}: {:?}", builder.block_number(), error);
+					// An extrinsic which does not fit in this block may fit in the next one, and one
+					// whose nonce is too high may follow an extrinsic which is yet to be included. Any
+					// other extrinsic is removed from the pool.
+					if !matches!(
+						error,
+						support::BlockError::ExhaustsResources { .. } | support::BlockError::FutureNonce { .. }
+					) {
+						invalid.push(extrinsic);
+					}
+				},
+			}
+		}
+		let block = builder.finalize();
+		// Blocks are shared with other nodes in their encoded form.
+		let encoded = codec::Encode::encode(&block);
+		let block = codec::decode_all::<types::Block>(&encoded).expect("valid encoding");
+		let block_number = block.header.block_number;
+		if let Err(error) = runtime.execute_block(block) {
+			// Built blocks are valid, so the block can only fail to be written to the state.
+			eprintln!("failed to import block {}: {:?}", block_number, error);
+			std::process::exit(1);
+		}
+		pool.prune(&runtime, &invalid);
+		println!("Events in block {} ({} bytes):", block_number, encoded.len());
+		for record in runtime.system.events() {
+			println!("\t{:?}: {:?}", record.phase, record.event);
+		}
+	}
+
+	println!("Block number: {}", runtime.system.block_number());
+	let treasury = crypto::Pair::from_seed("treasury");
+	for (name, pair) in [("alice", &alice), ("bob", &bob), ("charlie", &charlie), ("treasury", &treasury)] {
+		println!(
+			"\t{}: balance {}, nonce {}",
+			name,
+			runtime.balances.free_balance(&pair.public()),
+			runtime.system.nonce(&pair.public()),
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// The genesis config of a chain where `alice` has a balance of 100.
+	fn genesis(alice: &crypto::Pair) -> RuntimeGenesisConfig {
+		RuntimeGenesisConfig {
+			balances: balances::GenesisConfig { balances: vec![(alice.public(), 100)], ..Default::default() },
+			..Default::default()
+		}
+	}
+
+	fn new_runtime(alice: &crypto::Pair) -> Runtime {
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		runtime.build_genesis(&genesis(alice)).unwrap();
+		runtime
+	}
+
+	fn transfer(to: &crypto::Pair, amount: types::Balance) -> RuntimeCall {
+		RuntimeCall::balances(balances::Call::transfer { to: to.public(), amount })
+	}
+
+	#[test]
+	fn execute_block_checks_header() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
+
+		let block = runtime
+			.build_block(vec![signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30))])
+			.unwrap();
+
+		let mut bad_block = block.clone();
+		bad_block.header.parent_hash = [1; 32];
+		assert_eq!(runtime.execute_block(bad_block), Err(support::BlockError::InvalidParentHash));
+
+		let mut bad_block = block.clone();
+		bad_block.extrinsics.clear();
+		assert_eq!(runtime.execute_block(bad_block), Err(support::BlockError::InvalidExtrinsicsRoot));
+
+		let mut bad_block = block.clone();
+		bad_block.header.state_root = [1; 32];
+		assert_eq!(runtime.execute_block(bad_block), Err(support::BlockError::InvalidStateRoot));
+
+		// None of the changes made by the rejected blocks are kept.
+		assert_eq!(runtime.system.block_number(), 0);
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 100);
+
+		let hash = support::blake2_256(&codec::Encode::encode(&block.header));
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.system.block_number(), 1);
+		assert_eq!(runtime.system.block_hash(&1), Some(hash));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 70);
+	}
+
+	#[test]
+	fn execute_block_checks_nonces() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
+
+		assert_eq!(
+			runtime
+				.build_block(vec![signed_extrinsic(&runtime, &alice, 1, transfer(&bob, 0))])
+				.unwrap_err(),
+			support::BlockError::FutureNonce { extrinsic_index: 0 }
+		);
+
+		let block = runtime
+			.build_block(vec![
+				signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 0)),
+				signed_extrinsic(&runtime, &alice, 1, transfer(&bob, 0)),
+			])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.system.nonce(&alice.public()), 2);
+
+		// Replaying an extrinsic which was already included is rejected.
+		let mut replay = runtime
+			.build_block(vec![signed_extrinsic(&runtime, &alice, 2, transfer(&bob, 0))])
+			.unwrap();
+		replay.extrinsics = vec![signed_extrinsic(&runtime, &alice, 1, transfer(&bob, 0))];
+		replay.header.extrinsics_root = support::blake2_256(&codec::Encode::encode(&replay.extrinsics));
+		assert_eq!(
+			runtime.execute_block(replay),
+			Err(support::BlockError::StaleNonce { extrinsic_index: 0 })
+		);
+		assert_eq!(runtime.system.nonce(&alice.public()), 2);
+	}
+
+	#[test]
+	fn extrinsics_of_reaped_accounts_cannot_be_replayed() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut config = genesis(&alice);
+		config.balances.balances.push((bob.public(), 1_000));
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		runtime.build_genesis(&config).unwrap();
+
+		let signed = signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30));
+		let block = runtime.build_block(vec![signed.clone()]).unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+
+		// Alice sends everything to bob and is reaped, then bob funds her again.
+		let transfer_all = RuntimeCall::balances(balances::Call::transfer_all { to: bob.public(), keep_alive: false });
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &alice, 1, transfer_all)]).unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.system.providers(&alice.public()), 0);
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &bob, 0, transfer(&alice, 500))]).unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 500);
+
+		// Her nonce started over, but the transfer she signed before she was reaped cannot be
+		// replayed, since she did not exist yet at its checkpoint in her new life.
+		assert_eq!(runtime.system.nonce(&alice.public()), 0);
+		assert_eq!(runtime.system.created(&alice.public()), Some(3));
+		assert_eq!(
+			runtime.build_block(vec![signed]).unwrap_err(),
+			support::BlockError::BadCheckpoint { extrinsic_index: 0 }
+		);
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 500);
 
-    runtime.execute_block(block_1).expect("invalid block");
-	runtime.execute_block(block_2).expect("invalid block");
-	runtime.execute_block(block_3).expect("invalid block");
+		// Extrinsics signed since then are valid, but only once their checkpoint is imported.
+		let mut signed = signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30));
+		signed.signature.as_mut().unwrap().checkpoint = 4;
+		assert_eq!(
+			runtime.build_block(vec![signed]).unwrap_err(),
+			support::BlockError::BadCheckpoint { extrinsic_index: 0 }
+		);
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30))]).unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 470);
+	}
 
-    println!("{:#?}", runtime);
+	#[test]
+	fn execute_block_checks_signatures() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
 
-}
\ No newline at end of file
+		// Bob cannot sign an extrinsic on behalf of alice.
+		let mut forged = signed_extrinsic(&runtime, &bob, 0, transfer(&bob, 100));
+		forged.signature.as_mut().unwrap().caller = alice.public();
+		assert_eq!(
+			runtime.build_block(vec![forged]).unwrap_err(),
+			support::BlockError::BadSignature { extrinsic_index: 0 }
+		);
+
+		// Nobody pays for an unsigned extrinsic, so it can only make the calls which allow it.
+		let unsigned = support::Extrinsic { signature: None, call: transfer(&bob, 100) };
+		assert_eq!(
+			runtime.build_block(vec![unsigned.clone()]).unwrap_err(),
+			support::BlockError::Unsigned { extrinsic_index: 0 }
+		);
+		let mut block = runtime.build_block(vec![]).unwrap();
+		block.extrinsics.push(unsigned);
+		block.header.extrinsics_root = support::blake2_256(&codec::Encode::encode(&block.extrinsics));
+		assert_eq!(runtime.execute_block(block), Err(support::BlockError::Unsigned { extrinsic_index: 0 }));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 100);
+	}
+
+	#[test]
+	fn execute_block_checks_weight() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
+
+		use support::GetDispatchInfo;
+		assert_eq!(transfer(&bob, 0).get_dispatch_info().weight, 20_000);
+		let claim = |claim: &str| {
+			RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: claim.into() })
+		};
+		assert_eq!(claim("").get_dispatch_info().weight, 10_100);
+		assert_eq!(claim("hello").get_dispatch_info().weight, 10_600);
+
+		// A block fits 50 transfers.
+		let extrinsics = (0..51)
+			.map(|nonce| signed_extrinsic(&runtime, &alice, nonce, transfer(&bob, 1)))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			runtime.build_block(extrinsics.clone()).unwrap_err(),
+			support::BlockError::ExhaustsResources { extrinsic_index: 50 }
+		);
+
+		let mut block = runtime.build_block(extrinsics[..50].to_vec()).unwrap();
+		let mut heavy_block = block.clone();
+		heavy_block.extrinsics = extrinsics.clone();
+		heavy_block.header.extrinsics_root =
+			support::blake2_256(&codec::Encode::encode(&heavy_block.extrinsics));
+		assert_eq!(
+			runtime.execute_block(heavy_block),
+			Err(support::BlockError::ExhaustsResources { extrinsic_index: 50 })
+		);
+		assert_eq!(runtime.system.block_number(), 0);
+
+		assert_eq!(runtime.execute_block(block.clone()), Ok(()));
+		assert_eq!(runtime.system.block_weight(), 1_000_000);
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 50);
+
+		// The weight of the previous block does not count towards the next one.
+		block = runtime.build_block(extrinsics[50..].to_vec()).unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.system.block_weight(), 20_000);
+	}
+
+	#[test]
+	fn execute_block_charges_fees() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let treasury = crypto::Pair::from_seed("treasury");
+		let mut config = genesis(&alice);
+		config.balances.balances = vec![(alice.public(), 100_000)];
+		config.transaction_payment = transaction_payment::GenesisConfig {
+			base_fee: 1_000,
+			weight_fee: 1,
+			length_fee: 0,
+			treasury: Some(treasury.public()),
+		};
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		runtime.build_genesis(&config).unwrap();
+
+		// The fee of a transfer is the base fee, its weight of 20_000, and the tip.
+		let block = runtime
+			.build_block(vec![tipped_extrinsic(&runtime, &alice, 0, 50, transfer(&bob, 30))])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 100_000 - 30 - 21_050);
+		assert_eq!(runtime.balances.free_balance(&treasury.public()), 21_050);
+
+		// A claim which fails only pays for the weight it used, and the claim which succeeds
+		// reserves its deposit.
+		let claim = RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+			claim: "hello".to_string(),
+		});
+		let block = runtime
+			.build_block(vec![
+				signed_extrinsic(&runtime, &alice, 1, claim.clone()),
+				signed_extrinsic(&runtime, &alice, 2, claim),
+			])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 78_920 - 11_600 - 11_000 - 10);
+		assert_eq!(support::ReservableCurrency::reserved_balance(&runtime.balances, &alice.public()), 10);
+		assert_eq!(runtime.balances.free_balance(&treasury.public()), 21_050 + 11_600 + 11_000);
+		assert_eq!(runtime.system.block_weight(), 10_600 + 10_000);
+		assert!(matches!(
+			runtime.system.events()[4].event,
+			RuntimeEvent::transaction_payment(transaction_payment::Event::TransactionFeePaid {
+				actual_fee: 11_000,
+				..
+			})
+		));
+
+		// Bob cannot pay the fee of a transfer, so it cannot be included in a block.
+		assert_eq!(
+			runtime
+				.build_block(vec![signed_extrinsic(&runtime, &bob, 0, transfer(&alice, 10))])
+				.unwrap_err(),
+			support::BlockError::CannotPayFees { extrinsic_index: 0 }
+		);
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 30);
+	}
+
+	#[test]
+	fn pallet_instances_are_separate() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut config = genesis(&alice);
+		config.credits.balances = vec![(alice.public(), 500)];
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		runtime.build_genesis(&config).unwrap();
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 100);
+		assert_eq!(runtime.credits.free_balance(&alice.public()), 500);
+
+		// Both instances have the same calls, but under a different pallet index.
+		let credit_transfer = RuntimeCall::credits(balances::Call::transfer { to: bob.public(), amount: 200 });
+		assert_eq!(codec::Encode::encode(&credit_transfer)[0], 4);
+		let block = runtime
+			.build_block(vec![signed_extrinsic(&runtime, &alice, 0, credit_transfer)])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.credits.free_balance(&alice.public()), 300);
+		assert_eq!(runtime.credits.free_balance(&bob.public()), 200);
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 0);
+		assert!(runtime.system.events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::credits(balances::Event::Transfer { amount: 200, .. })
+		)));
+
+		// Errors report the index of the instance which returned them.
+		let call = RuntimeCall::credits(balances::Call::transfer { to: alice.public(), amount: 300 });
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &bob, 0, call)]).unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		let error = support::DispatchError::Module(support::ModuleError {
+			index: 4,
+			error: 0,
+			message: "InsufficientBalance",
+		});
+		assert!(runtime.system.events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::system(system::Event::ExtrinsicFailed { error: e }) if e == error
+		)));
+	}
+
+	#[test]
+	fn errors_keep_the_index_of_their_pallet() {
+		use support::PalletInfo;
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
+		assert_eq!(Runtime::index::<proof_of_existence::Pallet<Runtime>>(), Some(2));
+		assert_eq!(Runtime::index::<balances::Pallet<Runtime, support::Instance1>>(), Some(4));
+		assert_eq!(Runtime::index::<system::Pallet<Runtime>>(), None);
+
+		// A currency tags the errors it returns to other pallets with its own index, which the
+		// runtime keeps when the other pallet returns them from its call.
+		let error = |index| {
+			support::DispatchError::Module(support::ModuleError { index, error: 0, message: "InsufficientBalance" })
+		};
+		assert_eq!(support::ReservableCurrency::reserve(&mut runtime.balances, &bob.public(), 1), Err(error(1)));
+		assert_eq!(
+			support::Currency::transfer(
+				&mut runtime.credits,
+				&bob.public(),
+				&alice.public(),
+				1,
+				support::ExistenceRequirement::AllowDeath,
+			),
+			Err(error(4))
+		);
+		assert_eq!(error(1).with_pallet_index(2), error(1));
+	}
+
+	#[test]
+	fn sudo_dispatches_calls() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut config = genesis(&alice);
+		config.balances.balances = vec![(alice.public(), 100), (bob.public(), 100)];
+		config.sudo.key = Some(alice.public());
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		runtime.build_genesis(&config).unwrap();
+		let sudo = |call| RuntimeCall::sudo(sudo::Call::sudo { call: Box::new(call) });
+		// The events of the last block, other than fee payments.
+		let block_events = |runtime: &Runtime| {
+			runtime
+				.system
+				.events()
+				.iter()
+				.map(|record| record.event.clone())
+				.filter(|event| !matches!(event, RuntimeEvent::transaction_payment(_)))
+				.collect::<Vec<_>>()
+		};
+		let set_balance = |who: &crypto::Pair, amount| {
+			RuntimeCall::balances(balances::Call::force_set_balance { who: who.public(), amount })
+		};
+
+		// Root calls cannot be made directly, but the key can make them through sudo.
+		use support::GetDispatchInfo;
+		assert_eq!(sudo(set_balance(&bob, 500)).get_dispatch_info().weight, 10_000 + 10_000);
+		let block = runtime
+			.build_block(vec![
+				signed_extrinsic(&runtime, &alice, 0, set_balance(&bob, 500)),
+				signed_extrinsic(&runtime, &alice, 1, sudo(set_balance(&bob, 500))),
+			])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 500);
+		let events = block_events(&runtime);
+		assert!(matches!(
+			events[0],
+			RuntimeEvent::system(system::Event::ExtrinsicFailed { error: support::DispatchError::BadOrigin })
+		));
+		assert!(matches!(events[1], RuntimeEvent::balances(balances::Event::BalanceSet { amount: 500, .. })));
+		assert!(matches!(events[2], RuntimeEvent::sudo(sudo::Event::Sudid { sudo_result: Ok(()) })));
+		assert!(matches!(events[3], RuntimeEvent::system(system::Event::ExtrinsicSuccess)));
+
+		// A nested call which fails is rolled back, but the sudo call succeeds and reports its error.
+		let force_transfer = |from: &crypto::Pair, to: &crypto::Pair, amount| {
+			RuntimeCall::balances(balances::Call::force_transfer { from: from.public(), to: to.public(), amount })
+		};
+		let block = runtime
+			.build_block(vec![
+				signed_extrinsic(&runtime, &alice, 2, sudo(force_transfer(&bob, &alice, 1_000))),
+				signed_extrinsic(&runtime, &bob, 0, sudo(force_transfer(&bob, &alice, 100))),
+			])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 500);
+		let error = |index, error, message| {
+			support::DispatchError::Module(support::ModuleError { index, error, message })
+		};
+		let events = block_events(&runtime);
+		assert!(matches!(
+			events[0],
+			RuntimeEvent::sudo(sudo::Event::Sudid { sudo_result: Err(e) }) if e == error(1, 0, "InsufficientBalance")
+		));
+		assert!(matches!(events[1], RuntimeEvent::system(system::Event::ExtrinsicSuccess)));
+		assert!(matches!(
+			events[2],
+			RuntimeEvent::system(system::Event::ExtrinsicFailed { error: e }) if e == error(5, 0, "RequireSudo")
+		));
+
+		// The key can act on behalf of any account, and hand over its role.
+		let sudo_as = RuntimeCall::sudo(sudo::Call::sudo_as { who: bob.public(), call: Box::new(transfer(&alice, 50)) });
+		let set_key = RuntimeCall::sudo(sudo::Call::set_key { new: bob.public() });
+		let block = runtime
+			.build_block(vec![
+				signed_extrinsic(&runtime, &alice, 3, sudo_as),
+				signed_extrinsic(&runtime, &alice, 4, set_key),
+				signed_extrinsic(&runtime, &bob, 1, sudo(set_balance(&alice, 0))),
+			])
+			.unwrap();
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 450);
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 0);
+		// Emptying the account reaped it, along with its nonce.
+		assert_eq!((runtime.system.providers(&alice.public()), runtime.system.nonce(&alice.public())), (0, 0));
+		assert_eq!(runtime.sudo.key(), Some(bob.public()));
+		assert!(runtime.system.events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::sudo(sudo::Event::SudoAsDone { sudo_result: Ok(()) })
+		)));
+	}
+
+	#[test]
+	fn blocks_round_trip_through_codec() {
+		use codec::{decode_all, Encode};
+
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&alice);
+
+		let call = RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+			claim: "Hello, world!".to_string(),
+		});
+		let encoded = call.encode();
+		// The index of the pallet, the index of the call, and then the arguments.
+		assert_eq!(&encoded[..3], &[2, 0, 13 << 2]);
+		assert_eq!(decode_all::<RuntimeCall>(&encoded).unwrap().encode(), encoded);
+
+		let block = runtime
+			.build_block(vec![
+				signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30)),
+				signed_extrinsic(&runtime, &alice, 1, call),
+			])
+			.unwrap();
+		let encoded = block.encode();
+		let decoded = decode_all::<types::Block>(&encoded).unwrap();
+		assert_eq!(decoded.header, block.header);
+		assert_eq!(decoded.encode(), encoded);
+
+		// Unknown pallets and calls are rejected.
+		assert_eq!(decode_all::<RuntimeCall>(&[9, 0]).unwrap_err(), codec::Error::InvalidVariant);
+		assert_eq!(decode_all::<RuntimeCall>(&[1, 9]).unwrap_err(), codec::Error::InvalidVariant);
+	}
+
+	#[test]
+	fn genesis_from_chain_spec() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let spec = chain_spec::ChainSpec::from_json(
+			r#"{
+				"name": "Test",
+				"genesis": {
+					"system": { "block_number": 5 },
+					"balances": { "balances": [["//alice", 100], ["//bob", 50]] },
+					"proof_of_existence": { "claims": [["Hello, world!", "//bob"]] }
+				}
+			}"#,
+		)
+		.unwrap();
+
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		assert_eq!(runtime.build_genesis(&spec.genesis), Ok(()));
+		assert_eq!(runtime.system.block_number(), 5);
+		assert_eq!(runtime.system.block_hash(&5), runtime.system.genesis_hash());
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 50);
+		assert_eq!(
+			runtime.proof_of_existence.get_claim(&"Hello, world!".to_string()),
+			Some(bob.public())
+		);
+
+		// The first block follows the genesis block.
+		let block = runtime
+			.build_block(vec![signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30))])
+			.unwrap();
+		assert_eq!(block.header.block_number, 6);
+		assert_eq!(Some(block.header.parent_hash), runtime.system.genesis_hash());
+		assert_eq!(runtime.execute_block(block), Ok(()));
+
+		// An invalid genesis config leaves the storage untouched.
+		let mut config = spec.genesis.clone();
+		config.balances.balances.push((alice.public(), 1));
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		let empty_root = runtime.state_root();
+		assert_eq!(runtime.build_genesis(&config), Err(support::GenesisError::DuplicateAccount));
+		assert_eq!(runtime.state_root(), empty_root);
+	}
+
+	#[test]
+	fn state_survives_restart() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let claim = || {
+			RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+				claim: "Hello, world!".to_string(),
+			})
+		};
+		let path = std::env::temp_dir().join(format!("runtime-test-{}.log", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let open = || Runtime::new(storage::Storage::new(storage::FileBackend::open(&path).unwrap()));
+
+		let mut runtime = open();
+		runtime.build_genesis(&genesis(&alice)).unwrap();
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30))]).unwrap();
+		runtime.execute_block(block).unwrap();
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &bob, 0, claim())]).unwrap();
+		runtime.execute_block(block).unwrap();
+		let state_root = runtime.state_root();
+		drop(runtime);
+
+		let mut runtime = open();
+		assert_eq!(runtime.build_genesis(&genesis(&alice)), Err(support::GenesisError::AlreadyBuilt));
+		assert_eq!(runtime.system.block_number(), 2);
+		assert_eq!(runtime.state_root(), state_root);
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 70);
+		// Bob reserved the deposit of the claim.
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 20);
+		assert_eq!(runtime.system.nonce(&alice.public()), 1);
+		assert_eq!(runtime.proof_of_existence.get_claim(&"Hello, world!".to_string()), Some(bob.public()));
+
+		// The chain continues from the block it stopped at.
+		let block = runtime
+			.build_block(vec![signed_extrinsic(&runtime, &alice, 1, transfer(&bob, 30))])
+			.unwrap();
+		assert_eq!(block.header.block_number, 3);
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 50);
+
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	/// A backend whose commits fail while `failing` is set.
+	struct FailingBackend {
+		inner: storage::MemoryBackend,
+		failing: std::rc::Rc<std::cell::Cell<bool>>,
+	}
+
+	impl storage::Backend for FailingBackend {
+		fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+			self.inner.get(key)
+		}
+
+		fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+			self.inner.pairs()
+		}
+
+		fn commit(&mut self, changes: storage::Changes) -> std::io::Result<()> {
+			if self.failing.get() {
+				return Err(std::io::ErrorKind::StorageFull.into())
+			}
+			self.inner.commit(changes)
+		}
+	}
+
+	#[test]
+	fn storage_errors_are_returned() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let failing = std::rc::Rc::new(std::cell::Cell::new(true));
+		let backend = FailingBackend { inner: storage::MemoryBackend::default(), failing: failing.clone() };
+		let mut runtime = Runtime::new(storage::Storage::new(backend));
+
+		assert_eq!(
+			runtime.build_genesis(&genesis(&alice)),
+			Err(support::GenesisError::Storage(std::io::ErrorKind::StorageFull))
+		);
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 0);
+		failing.set(false);
+		runtime.build_genesis(&genesis(&alice)).unwrap();
+
+		let block = runtime.build_block(vec![signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 30))]).unwrap();
+		failing.set(true);
+		assert_eq!(
+			runtime.execute_block(block.clone()),
+			Err(support::BlockError::Storage(std::io::ErrorKind::StorageFull))
+		);
+		// The block is not imported, so the state still matches the backend.
+		assert_eq!(runtime.system.block_number(), 0);
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 100);
+		assert!(runtime.system.events().is_empty());
+
+		failing.set(false);
+		assert_eq!(runtime.execute_block(block), Ok(()));
+		assert_eq!(runtime.balances.free_balance(&bob.public()), 30);
+	}
+
+	#[test]
+	fn metadata_describes_pallets() {
+		let metadata = Runtime::metadata();
+		let names = metadata.pallets.iter().map(|pallet| (pallet.name, pallet.index)).collect::<Vec<_>>();
+		assert_eq!(
+			names,
+			vec![
+				("system", 0),
+				("balances", 1),
+				("proof_of_existence", 2),
+				("transaction_payment", 3),
+				("credits", 4),
+				("sudo", 5),
+			]
+		);
+
+		let balances = &metadata.pallets[1];
+		assert_eq!(
+			balances.calls[1],
+			metadata::CallMetadata {
+				name: "transfer",
+				index: 1,
+				args: vec![
+					metadata::FieldMetadata { name: "to", ty: "T::AccountId" },
+					metadata::FieldMetadata { name: "amount", ty: "T::Balance" },
+				],
+				docs: vec!["Transfer `amount` from the caller to `to`."],
+			}
+		);
+		// Calls are encoded with the indices in the metadata.
+		let bob = crypto::Pair::from_seed("bob");
+		assert_eq!(codec::Encode::encode(&transfer(&bob, 1))[..2], [balances.index, balances.calls[1].index]);
+		assert_eq!(balances.events[0].name, "Transfer");
+		assert_eq!(balances.errors[0].name, "InsufficientBalance");
+
+		// The index of an error in the metadata is the index in the `ModuleError` it returns.
+		let poe = &metadata.pallets[2];
+		let error = support::DispatchError::from(proof_of_existence::Error::NotClaimOwner);
+		let support::DispatchError::Module(error) = error else { panic!("not a module error") };
+		assert_eq!(poe.errors[error.error as usize].name, "NotClaimOwner");
+		assert!(metadata.pallets[3].calls.is_empty());
+
+		let json = serde_json::to_value(&metadata).unwrap();
+		assert_eq!(json["pallets"][1]["calls"][1]["args"][0]["type"], "T::AccountId");
+	}
+}
@@ -0,0 +1,58 @@
+//! A description of the pallets of the runtime, generated by the macros, which tools use to build
+//! extrinsics and show events without knowing the runtime in advance.
+//!
+//! Types are described by their name as written in the source of the pallet, like `T::AccountId`.
+
+/// Every pallet of the runtime, in the order they are declared.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct RuntimeMetadata {
+	pub pallets: Vec<PalletMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PalletMetadata {
+	/// The name of the pallet in the runtime.
+	pub name: &'static str,
+	/// The index of the pallet, which prefixes its calls and errors.
+	pub index: u8,
+	pub calls: Vec<CallMetadata>,
+	pub events: Vec<EventMetadata>,
+	pub errors: Vec<ErrorMetadata>,
+}
+
+/// A callable function of a pallet.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct CallMetadata {
+	pub name: &'static str,
+	/// The index of the call in its pallet, which prefixes its encoded arguments.
+	pub index: u8,
+	/// The arguments of the call, not including the origin, in the order they are encoded.
+	pub args: Vec<FieldMetadata>,
+	/// The doc comments of the call, one line per item.
+	pub docs: Vec<&'static str>,
+}
+
+/// An event which a pallet can emit.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EventMetadata {
+	pub name: &'static str,
+	pub fields: Vec<FieldMetadata>,
+	pub docs: Vec<&'static str>,
+}
+
+/// An error which the calls of a pallet can return.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ErrorMetadata {
+	pub name: &'static str,
+	/// The index of the error, as found in a `support::ModuleError`.
+	pub index: u8,
+	pub docs: Vec<&'static str>,
+}
+
+/// A named argument of a call, or field of an event.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct FieldMetadata {
+	pub name: &'static str,
+	#[serde(rename = "type")]
+	pub ty: &'static str,
+}
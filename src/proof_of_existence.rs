@@ -1,85 +1,244 @@
 use core::fmt::Debug;
-use std::collections::BTreeMap;
+use crate::codec::{Decode, Encode};
+use crate::storage::{Storage, StorageMap};
+use crate::support::{Currency, DispatchError, DispatchResultWithPostInfo, GenesisError, ReservableCurrency, UsesPallet};
+use crate::system::ensure_signed;
+
+/// The balance of the currency which deposits are reserved in.
+type BalanceOf<T> = <<T as Config>::Currency as Currency>::Balance;
 
 pub trait Config: crate::system::Config {
 	/// The type which represents the content that can be claimed using this pallet.
 	/// Could be the content directly as bytes, or better yet the hash of that content.
 	/// We leave that decision to the runtime developer.
-	type Content: Debug + Ord;
+	type Content: Debug + Ord + Clone + Encode + Decode;
+	/// The currency which deposits are reserved in. It is a pallet of the runtime, which the runtime
+	/// wires into this pallet before dispatching its calls.
+	type Currency: ReservableCurrency<AccountId = Self::AccountId> + Debug;
+	/// The deposit reserved from the owner of a claim, until it is revoked.
+	const CLAIM_DEPOSIT: BalanceOf<Self>;
+}
+
+/// The events emitted by the Proof of Existence Module.
+#[macros::event(generate_deposit)]
+#[derive(Debug, macros::CloneNoBound, PartialEq)]
+pub enum Event<T: Config> {
+	/// `owner` created a claim on `claim`.
+	ClaimCreated { owner: T::AccountId, claim: T::Content },
+	/// `owner` revoked their claim on `claim`.
+	ClaimRevoked { owner: T::AccountId, claim: T::Content },
+}
+
+/// The errors returned by the Proof of Existence Module.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// This content has already been claimed.
+	AlreadyClaimed,
+	/// This content has not been claimed.
+	NoSuchClaim,
+	/// This content is claimed by another account.
+	NotClaimOwner,
+}
+
+/// The genesis state of the Proof of Existence Module.
+#[derive(Debug, macros::CloneNoBound, serde::Deserialize)]
+#[serde(
+	bound(deserialize = "T::Content: serde::Deserialize<'de>, T::AccountId: serde::Deserialize<'de>"),
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The claims which exist at genesis, and their owner.
+	pub claims: Vec<(T::Content, T::AccountId)>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { claims: Vec::new() }
+	}
 }
 
+const PALLET: &str = "ProofOfExistence";
+
 /// This is the Proof of Existence Module.
 /// It is a simple module that allows accounts to claim existence of some data.
+#[macros::storage]
 #[derive(Debug)]
 pub struct Pallet<T: Config> {
-	/// A simple storage map from content to the owner of that content.
+	/// The storage shared with the other pallets, where the events of this pallet are deposited.
+	storage: Storage,
+	/// A map from content to the owner of that content.
 	/// Accounts can make multiple different claims, but each claim can only have one owner.
-	/* TODO: Add a field `claims` which is a `BTreeMap` fom `T::Content` to `T::AccountId`. */
-    claims: BTreeMap<T::Content, T::AccountId>,
-
+	claims: StorageMap<T::Content, T::AccountId>,
+	/// The deposit reserved for each claim. Claims made at genesis have none.
+	deposits: StorageMap<T::Content, BalanceOf<T>>,
+	/// The currency, once the runtime wired it into this pallet.
+	currency: Option<T::Currency>,
 }
 
 impl<T: Config> Pallet<T> {
-	/// Create a new instance of the Proof of Existence Module.
-	pub fn new() -> Self {
-		/* TODO: Return a new instance of the `Pallet` struct. */
-        Self { claims: BTreeMap::new() }
-    }
+	pub fn get_claim(&self, content: &T::Content) -> Option<T::AccountId> {
+		self.claims.get(content)
+	}
 
-    pub fn get_claim(&self, content: &T::Content) -> Option<&T::AccountId> {
-            self.claims.get(content)
-    }
+	/// Build the genesis state of the pallet. Every content may only be claimed once.
+	pub fn build_genesis(&mut self, config: &GenesisConfig<T>) -> Result<(), GenesisError> {
+		for (claim, owner) in &config.claims {
+			if config.claims.iter().filter(|(other, _)| other == claim).count() > 1 {
+				return Err(GenesisError::DuplicateClaim)
+			}
+			self.claims.insert(claim, owner);
+		}
+		Ok(())
+	}
 
+	/// The currency which deposits are reserved in, which the runtime wires in before dispatching
+	/// calls.
+	fn currency(&mut self) -> &mut T::Currency {
+		self.currency.as_mut().expect("the runtime wires the currency in before dispatching calls")
+	}
+}
+
+// The Proof of Existence Module has no per-block logic.
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {}
+
+// The deposits of claims are reserved in the currency wired in by the runtime.
+impl<T: Config> UsesPallet<T::Currency> for Pallet<T> {
+	fn wire(&mut self, pallet: T::Currency) {
+		self.currency = Some(pallet);
+	}
 }
 
+// The Proof of Existence Module does not dispatch other calls.
+impl<T: Config, O, C> crate::support::DispatchNested<O, C> for Pallet<T> {}
+
 #[macros::call]
 impl<T: Config> Pallet<T> {
+	/// Claim `claim` for the caller, who reserves `T::CLAIM_DEPOSIT` until revoking it. The weight
+	/// grows with the size of the claim, which is stored.
+	#[call_index(0)]
+	#[weight(10_000 + 100 * crate::codec::Encode::encode(claim).len() as u64)]
+	pub fn create_claim(&mut self, origin: T::RuntimeOrigin, claim: T::Content) -> DispatchResultWithPostInfo {
+		let caller = ensure_signed(origin)?;
+		if self.claims.contains_key(&claim) {
+			// Nothing is stored, so only the base weight is used.
+			return Err(DispatchError::from(Error::AlreadyClaimed).with_weight(10_000))
+		}
+		// The currency tells why the caller cannot reserve the deposit.
+		self.currency().reserve(&caller, T::CLAIM_DEPOSIT)?;
+		self.claims.insert(&claim, &caller);
+		self.deposits.insert(&claim, &T::CLAIM_DEPOSIT);
+		self.deposit_event(Event::ClaimCreated { owner: caller, claim });
+		Ok(().into())
+	}
 
-    pub fn create_claim(&mut self, caller: T::AccountId, claim: T::Content) -> crate::support::DispatchResult {
-        if self.claims.contains_key(&claim) {
-            return Err("Claim already exists");
-        }
-        self.claims.insert(claim, caller);
-        Ok(())
-    }
-
-    pub fn revoke_claim(&mut self, caller: T::AccountId, claim: T::Content) -> crate::support::DispatchResult {
-        let owner = self.get_claim(&claim).ok_or("claim does not exist")?;
-        if caller != *owner {
-            return Err("this content is owned by someone else");
-        }
-        self.claims.remove(&claim);
-        Ok(())
-    }
+	/// Revoke the claim of the caller on `claim`, and unreserve its deposit.
+	#[call_index(1)]
+	#[weight(10_000)]
+	pub fn revoke_claim(&mut self, origin: T::RuntimeOrigin, claim: T::Content) -> crate::support::DispatchResult {
+		let caller = ensure_signed(origin)?;
+		let owner = self.get_claim(&claim).ok_or(Error::NoSuchClaim)?;
+		if caller != owner {
+			return Err(Error::NotClaimOwner.into())
+		}
+		self.claims.remove(&claim);
+		if let Some(deposit) = self.deposits.get(&claim) {
+			self.deposits.remove(&claim);
+			self.currency().unreserve(&caller, deposit);
+		}
+		self.deposit_event(Event::ClaimRevoked { owner: caller, claim });
+		Ok(())
+	}
 }
 
-
 #[cfg(test)]
 mod test {
+	use crate::support::{Origin, ReservableCurrency, UsesPallet};
+
+	#[derive(Debug, PartialEq)]
 	struct TestConfig;
 
 	impl super::Config for TestConfig {
-		type Content = &'static str;
+		type Content = String;
+		type Currency = crate::balances::Pallet<TestConfig>;
+		const CLAIM_DEPOSIT: u128 = 10;
+	}
+
+	impl crate::balances::Config for TestConfig {
+		type Balance = u128;
+		const EXISTENTIAL_DEPOSIT: u128 = 1;
 	}
 
 	impl crate::system::Config for TestConfig {
-		type AccountId = &'static str;
+		type AccountId = String;
 		type BlockNumber = u32;
 		type Nonce = u32;
+		type Signature = crate::support::TestSignature<String>;
+		type RuntimeOrigin = Origin<String>;
+		type RuntimeEvent = ();
+		type PalletInfo = ();
+		const BLOCK_WEIGHT_LIMIT: crate::support::Weight = u64::MAX;
+	}
+
+	fn hello() -> String {
+		"Hello, world!".to_string()
 	}
 
 	#[test]
 	fn basic_proof_of_existence() {
-        let mut poe = super::Pallet::<TestConfig>::new();
-        assert_eq!(poe.get_claim(&"Hello, world!"), None);
-		assert_eq!(poe.create_claim("alice", "Hello, world!"), Ok(()));
-		assert_eq!(poe.get_claim(&"Hello, world!"), Some(&"alice"));
+		let storage = crate::storage::Storage::in_memory();
+		let mut poe = super::Pallet::<TestConfig>::new(storage.clone());
+		poe.wire(crate::balances::Pallet::<TestConfig>::new(storage.clone()));
+		let mut balances = crate::balances::Pallet::<TestConfig>::new(storage);
+		let config = crate::balances::GenesisConfig::<TestConfig> {
+			balances: vec![("alice".to_string(), 100), ("bob".to_string(), 100), ("charlie".to_string(), 5)],
+			..Default::default()
+		};
+		balances.build_genesis(&config).unwrap();
+		let config = super::GenesisConfig::<TestConfig> {
+			claims: vec![("genesis".to_string(), "alice".to_string()), ("genesis".to_string(), "bob".to_string())],
+		};
+		assert_eq!(poe.build_genesis(&config), Err(crate::support::GenesisError::DuplicateClaim));
+		let config = super::GenesisConfig::<TestConfig> {
+			claims: vec![("genesis".to_string(), "alice".to_string())],
+		};
+		assert_eq!(poe.build_genesis(&config), Ok(()));
+		assert_eq!(poe.get_claim(&"genesis".to_string()), Some("alice".to_string()));
+		assert_eq!(poe.get_claim(&hello()), None);
+		assert_eq!(poe.create_claim(Origin::Signed("alice".to_string()), hello()), Ok(().into()));
+		assert_eq!(poe.get_claim(&hello()), Some("alice".to_string()));
+		assert_eq!(poe.currency().reserved_balance(&"alice".to_string()), 10);
 		assert_eq!(
-			poe.create_claim("bob", "Hello, world!"),
-			Err("Claim already exists")
+			poe.create_claim(Origin::Signed("charlie".to_string()), "poor".to_string()),
+			Err(crate::balances::Error::InsufficientBalance.into())
 		);
-		assert_eq!(poe.revoke_claim("alice", "Hello, world!"), Ok(()));
-		assert_eq!(poe.create_claim("bob", "Hello, world!"), Ok(()));
-
+		assert_eq!(
+			poe.create_claim(Origin::Signed("bob".to_string()), hello()),
+			Err(crate::support::DispatchError::from(super::Error::AlreadyClaimed).with_weight(10_000))
+		);
+		assert_eq!(
+			poe.revoke_claim(Origin::Signed("bob".to_string()), hello()),
+			Err(super::Error::NotClaimOwner.into())
+		);
+		assert_eq!(poe.revoke_claim(Origin::Signed("alice".to_string()), hello()), Ok(()));
+		assert_eq!(poe.currency().reserved_balance(&"alice".to_string()), 0);
+		// Claims made at genesis have no deposit to unreserve.
+		assert_eq!(poe.revoke_claim(Origin::Signed("alice".to_string()), "genesis".to_string()), Ok(()));
+		assert_eq!(
+			poe.revoke_claim(Origin::Signed("alice".to_string()), hello()),
+			Err(super::Error::NoSuchClaim.into())
+		);
+		assert_eq!(poe.create_claim(Origin::Signed("bob".to_string()), hello()), Ok(().into()));
+		assert_eq!(
+			poe.take_events(),
+			vec![
+				super::Event::ClaimCreated { owner: "alice".to_string(), claim: hello() },
+				super::Event::ClaimRevoked { owner: "alice".to_string(), claim: hello() },
+				super::Event::ClaimRevoked { owner: "alice".to_string(), claim: "genesis".to_string() },
+				super::Event::ClaimCreated { owner: "bob".to_string(), claim: hello() },
+			]
+		);
+		assert_eq!(balances.free_balance(&"alice".to_string()), 100);
+		assert_eq!(balances.reserved_balance(&"bob".to_string()), 10);
 	}
 }
\ No newline at end of file
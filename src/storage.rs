@@ -0,0 +1,898 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::codec::{decode_all, Decode, Encode};
+use crate::support::{self, Hash};
+
+/// A set of changes to the storage. A value of `None` removes the key.
+pub type Changes = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+/// A key-value store which holds the committed state of the runtime.
+pub trait Backend {
+	/// The value stored at `key`, if any.
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+	/// All the key-value pairs in the store, ordered by key.
+	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)>;
+	/// The key-value pairs whose key starts with `prefix`, ordered by key.
+	fn pairs_with_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.pairs().into_iter().filter(|(key, _)| key.starts_with(prefix)).collect()
+	}
+	/// Apply `changes` to the store. Either all of the changes are applied, or none of them.
+	fn commit(&mut self, changes: Changes) -> io::Result<()>;
+}
+
+/// A backend which keeps everything in memory. Used for tests, and for chains which do not need to
+/// survive a restart.
+#[derive(Default)]
+pub struct MemoryBackend {
+	pairs: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// The entries of `map` whose key starts with `prefix`, in key order.
+fn with_prefix<'a, V>(
+	map: &'a BTreeMap<Vec<u8>, V>,
+	prefix: &'a [u8],
+) -> impl Iterator<Item = (&'a Vec<u8>, &'a V)> {
+	map.range(prefix.to_vec()..).take_while(move |(key, _)| key.starts_with(prefix))
+}
+
+/// Apply `changes` to an in-memory map of the storage.
+fn apply(pairs: &mut BTreeMap<Vec<u8>, Vec<u8>>, changes: Changes) {
+	for (key, value) in changes {
+		match value {
+			Some(value) => pairs.insert(key, value),
+			None => pairs.remove(&key),
+		};
+	}
+}
+
+impl Backend for MemoryBackend {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.pairs.get(key).cloned()
+	}
+
+	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.pairs.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+	}
+
+	fn pairs_with_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		with_prefix(&self.pairs, prefix).map(|(k, v)| (k.clone(), v.clone())).collect()
+	}
+
+	fn commit(&mut self, changes: Changes) -> io::Result<()> {
+		apply(&mut self.pairs, changes);
+		Ok(())
+	}
+}
+
+/// A backend which persists the storage to an append-only log on disk.
+///
+/// Every commit appends one record to the log: the length of the encoded changes as a `u32`, the
+/// encoded changes, and their `blake2_256` hash. On open, the log is replayed into memory. A record
+/// which is incomplete or does not match its hash was interrupted while being written, so it and
+/// anything after it is discarded.
+///
+/// Since the log only grows, it is compacted every `compact_every` commits, by writing the whole
+/// state as a single record to a new file and replacing the log with it.
+pub struct FileBackend {
+	path: PathBuf,
+	file: File,
+	/// The length of the valid records in the log. Anything after it was left by a failed write.
+	len: u64,
+	pairs: BTreeMap<Vec<u8>, Vec<u8>>,
+	commits_since_compaction: usize,
+	compact_every: usize,
+}
+
+impl FileBackend {
+	/// The default number of commits between compactions of the log.
+	pub const COMPACT_EVERY: usize = 100;
+
+	/// Open the log at `path`, creating it if it does not exist.
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let mut bytes = Vec::new();
+		if path.exists() {
+			File::open(&path)?.read_to_end(&mut bytes)?;
+		}
+
+		let mut pairs = BTreeMap::new();
+		let mut input = &bytes[..];
+		while let Some(changes) = Self::read_record(&mut input) {
+			apply(&mut pairs, changes);
+		}
+
+		// Drop whatever is left after the last valid record.
+		let valid_len = (bytes.len() - input.len()) as u64;
+		let file = OpenOptions::new().create(true).append(true).open(&path)?;
+		file.set_len(valid_len)?;
+
+		Ok(Self {
+			path,
+			file,
+			len: valid_len,
+			pairs,
+			commits_since_compaction: 0,
+			compact_every: Self::COMPACT_EVERY,
+		})
+	}
+
+	fn read_record(input: &mut &[u8]) -> Option<Changes> {
+		let len = u32::decode(input).ok()? as usize;
+		if input.len() < len + 32 {
+			return None
+		}
+		let (payload, rest) = input.split_at(len);
+		let (hash, rest) = rest.split_at(32);
+		if support::blake2_256(payload) != hash {
+			return None
+		}
+		let changes = decode_all::<Vec<(Vec<u8>, Option<Vec<u8>>)>>(payload).ok()?;
+		*input = rest;
+		Some(changes.into_iter().collect())
+	}
+
+	/// Append a record of `changes` to `file`, and return the length of the record.
+	fn write_record(file: &mut File, changes: &Changes) -> io::Result<u64> {
+		let payload = changes.iter().collect::<Vec<_>>().encode();
+		let mut record = (payload.len() as u32).encode();
+		record.extend_from_slice(&payload);
+		record.extend_from_slice(&support::blake2_256(&payload));
+		file.write_all(&record)?;
+		file.sync_data()?;
+		Ok(record.len() as u64)
+	}
+
+	/// Replace the log with a single record holding the current state.
+	fn compact(&mut self) -> io::Result<()> {
+		let snapshot = self.pairs.iter().map(|(k, v)| (k.clone(), Some(v.clone()))).collect();
+		let tmp_path = self.path.with_extension("compact");
+		let result = OpenOptions::new()
+			.create(true)
+			.write(true)
+			.truncate(true)
+			.open(&tmp_path)
+			.and_then(|mut tmp| {
+				let len = Self::write_record(&mut tmp, &snapshot)?;
+				std::fs::rename(&tmp_path, &self.path)?;
+				Ok((tmp, len))
+			});
+		let (tmp, len) = result.inspect_err(|_| {
+			let _ = std::fs::remove_file(&tmp_path);
+		})?;
+		// Keep appending through the handle the new log was written with, so that no handle to the
+		// replaced log is left behind if the path cannot be opened again.
+		self.file = tmp;
+		self.len = len;
+		self.commits_since_compaction = 0;
+		Ok(())
+	}
+}
+
+impl Backend for FileBackend {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.pairs.get(key).cloned()
+	}
+
+	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.pairs.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+	}
+
+	fn pairs_with_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		with_prefix(&self.pairs, prefix).map(|(k, v)| (k.clone(), v.clone())).collect()
+	}
+
+	fn commit(&mut self, changes: Changes) -> io::Result<()> {
+		match Self::write_record(&mut self.file, &changes) {
+			Ok(len) => self.len += len,
+			Err(error) => {
+				// Cut the log back to its last valid record: a partial record would otherwise hide
+				// every record written after it when the log is replayed.
+				self.file = OpenOptions::new().append(true).open(&self.path)?;
+				self.file.set_len(self.len)?;
+				return Err(error)
+			},
+		}
+		apply(&mut self.pairs, changes);
+		self.commits_since_compaction += 1;
+		if self.commits_since_compaction >= self.compact_every {
+			// The changes are committed whether or not the log is compacted, so a failed compaction
+			// is only reported, and retried by the next commit.
+			if let Err(error) = self.compact() {
+				eprintln!("failed to compact {}: {}", self.path.display(), error);
+			}
+		}
+		Ok(())
+	}
+}
+
+struct Inner {
+	backend: Box<dyn Backend>,
+	/// Changes which are not yet committed to the backend. The first layer holds the changes which
+	/// will be committed by the next `flush`, and every open transaction adds a layer on top.
+	layers: Vec<Changes>,
+	/// The events deposited by pallets which were not taken yet, in the same layers as `layers`.
+	/// Events are not part of the state, so they are never committed to the backend.
+	events: Vec<Vec<Box<dyn Any>>>,
+	/// The state root, if it was computed since the last change to the state.
+	root: Option<Hash>,
+}
+
+/// A handle to the storage of the runtime, which every pallet reads and writes through.
+///
+/// Handles are cheap to clone, and all clones share the same storage. Writes are kept in memory
+/// until they are `flush`ed to the backend, and can be grouped into nested transactions.
+#[derive(Clone)]
+pub struct Storage(Rc<RefCell<Inner>>);
+
+impl core::fmt::Debug for Storage {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		f.debug_struct("Storage").finish_non_exhaustive()
+	}
+}
+
+impl Storage {
+	pub fn new(backend: impl Backend + 'static) -> Self {
+		Self(Rc::new(RefCell::new(Inner {
+			backend: Box::new(backend),
+			layers: vec![Changes::new()],
+			events: vec![Vec::new()],
+			root: None,
+		})))
+	}
+
+	/// A storage which is only kept in memory.
+	pub fn in_memory() -> Self {
+		Self::new(MemoryBackend::default())
+	}
+
+	/// The encoded value at `key`, taking uncommitted changes into account.
+	pub fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>> {
+		let inner = self.0.borrow();
+		for layer in inner.layers.iter().rev() {
+			if let Some(value) = layer.get(key) {
+				return value.clone()
+			}
+		}
+		inner.backend.get(key)
+	}
+
+	/// The value at `key`, if any.
+	///
+	/// Panics if the stored value cannot be decoded, which means the storage is corrupted.
+	pub fn get<V: Decode>(&self, key: &[u8]) -> Option<V> {
+		self.get_raw(key).map(|bytes| decode_all(&bytes).expect("storage is not corrupted"))
+	}
+
+	pub fn put<V: Encode>(&self, key: &[u8], value: &V) {
+		self.set_raw(key, Some(value.encode()));
+	}
+
+	pub fn remove(&self, key: &[u8]) {
+		self.set_raw(key, None);
+	}
+
+	fn set_raw(&self, key: &[u8], value: Option<Vec<u8>>) {
+		let mut inner = self.0.borrow_mut();
+		inner.root = None;
+		let layer = inner.layers.last_mut().expect("there is always at least one layer");
+		layer.insert(key.to_vec(), value);
+	}
+
+	/// Start a new transaction. Every transaction must be ended by either `commit_transaction` or
+	/// `rollback_transaction`.
+	pub fn start_transaction(&self) {
+		let mut inner = self.0.borrow_mut();
+		inner.layers.push(Changes::new());
+		inner.events.push(Vec::new());
+	}
+
+	/// Keep the changes made, and the events deposited, since the matching `start_transaction`.
+	pub fn commit_transaction(&self) {
+		let mut inner = self.0.borrow_mut();
+		assert!(inner.layers.len() > 1, "no transaction to commit");
+		let layer = inner.layers.pop().expect("checked above");
+		inner.layers.last_mut().expect("checked above").extend(layer);
+		let events = inner.events.pop().expect("events are layered like changes");
+		inner.events.last_mut().expect("checked above").extend(events);
+	}
+
+	/// Discard the changes made, and the events deposited, since the matching `start_transaction`.
+	pub fn rollback_transaction(&self) {
+		let mut inner = self.0.borrow_mut();
+		assert!(inner.layers.len() > 1, "no transaction to roll back");
+		if !inner.layers.pop().expect("checked above").is_empty() {
+			inner.root = None;
+		}
+		inner.events.pop();
+	}
+
+	/// Execute `f` as a storage transaction.
+	///
+	/// If `f` returns an error, all of the changes it made to the storage are discarded, so a failed
+	/// call leaves no trace of any partial changes it made.
+	pub fn with_transaction<R, E>(&self, f: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+		self.start_transaction();
+		let result = f();
+		if result.is_ok() {
+			self.commit_transaction();
+		} else {
+			self.rollback_transaction();
+		}
+		result
+	}
+
+	/// Deposit an event of a pallet, until the runtime takes it with `take_events`. An event deposited
+	/// in a transaction which is rolled back is discarded along with the changes.
+	pub fn deposit_event<E: 'static>(&self, event: E) {
+		let mut inner = self.0.borrow_mut();
+		inner.events.last_mut().expect("there is always at least one layer").push(Box::new(event));
+	}
+
+	/// Drain the events of type `E` which were deposited, in the order they were deposited.
+	pub fn take_events<E: 'static>(&self) -> Vec<E> {
+		let mut inner = self.0.borrow_mut();
+		let mut taken = Vec::new();
+		for layer in &mut inner.events {
+			let (matching, rest) = core::mem::take(layer).into_iter().partition(|event| event.is::<E>());
+			*layer = rest;
+			taken.extend(matching);
+		}
+		taken.into_iter().map(|event| *event.downcast::<E>().expect("only events of type `E` were taken")).collect()
+	}
+
+	/// Commit all changes outside of a transaction to the backend.
+	///
+	/// If the backend fails to commit them, the changes are discarded, so the storage still matches
+	/// the state held by the backend.
+	pub fn flush(&self) -> io::Result<()> {
+		let mut inner = self.0.borrow_mut();
+		assert_eq!(inner.layers.len(), 1, "cannot flush during a transaction");
+		let changes = core::mem::take(&mut inner.layers[0]);
+		let result = inner.backend.commit(changes);
+		if result.is_err() {
+			inner.root = None;
+		}
+		result
+	}
+
+	/// Every key-value pair in the storage, including uncommitted changes.
+	fn pairs(&self) -> BTreeMap<Vec<u8>, Vec<u8>> {
+		let inner = self.0.borrow();
+		let mut pairs = inner.backend.pairs().into_iter().collect::<BTreeMap<_, _>>();
+		for layer in &inner.layers {
+			apply(&mut pairs, layer.clone());
+		}
+		pairs
+	}
+
+	/// The key-value pairs whose key starts with `prefix`, in key order, including uncommitted
+	/// changes. Only the keys with the prefix are read, from the backend and from every layer.
+	pub fn pairs_with_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		let inner = self.0.borrow();
+		let mut pairs = inner.backend.pairs_with_prefix(prefix).into_iter().collect::<BTreeMap<_, _>>();
+		for layer in &inner.layers {
+			let changes = with_prefix(layer, prefix).map(|(k, v)| (k.clone(), v.clone())).collect();
+			apply(&mut pairs, changes);
+		}
+		pairs.into_iter().collect()
+	}
+
+	/// The hash of all key-value pairs in the storage, including uncommitted changes.
+	///
+	/// Computing the root reads and hashes the whole state, so it is cached until the state
+	/// changes: calling it again without writing in between is cheap, but the first call after a
+	/// write costs as much as the size of the state.
+	pub fn root(&self) -> Hash {
+		if let Some(root) = self.0.borrow().root {
+			return root
+		}
+		let root = support::blake2_256(&self.pairs().into_iter().collect::<Vec<_>>().encode());
+		self.0.borrow_mut().root = Some(root);
+		root
+	}
+}
+
+/// A pallet, or anything else, which is created on a handle to the storage. Since every pallet of
+/// the runtime reads and writes the same storage, the runtime creates the pallets it wires into the
+/// pallets which use them this way.
+pub trait FromStorage {
+	fn from_storage(storage: Storage) -> Self;
+}
+
+/// The key of a storage item which holds a single value, and the prefix of the keys of the entries
+/// of a map. The pallet and item names are length prefixed, so the keys of different items never
+/// collide.
+fn item_key(pallet: &str, item: &str) -> Vec<u8> {
+	(pallet, item).encode()
+}
+
+
+/// How a storage item is queried: what `get` returns, including when the item is empty.
+pub trait QueryKind<V> {
+	/// The type returned by `get`.
+	type Query;
+
+	fn from_value(value: V) -> Self::Query;
+	/// The value to store for `query`. `None` removes the item.
+	fn into_value(query: Self::Query) -> Option<V>;
+}
+
+/// `get` returns `None` when the item is empty, and setting it to `None` removes it.
+pub struct OptionQuery;
+
+impl<V> QueryKind<V> for OptionQuery {
+	type Query = Option<V>;
+
+	fn from_value(value: V) -> Option<V> {
+		Some(value)
+	}
+
+	fn into_value(query: Option<V>) -> Option<V> {
+		query
+	}
+}
+
+/// `get` returns a default value when the item is empty.
+pub struct ValueQuery;
+
+impl<V> QueryKind<V> for ValueQuery {
+	type Query = V;
+
+	fn from_value(value: V) -> V {
+		value
+	}
+
+	fn into_value(query: V) -> Option<V> {
+		Some(query)
+	}
+}
+
+/// The logic shared by the typed storage items, which all read and write values of type `V` at
+/// keys starting with the same prefix.
+struct Item<V, Q: QueryKind<V>> {
+	storage: Storage,
+	/// The key of a value, or the prefix of the keys of a map.
+	prefix: Vec<u8>,
+	/// The result of `get` when the item is empty.
+	on_empty: fn() -> Q::Query,
+	_value: PhantomData<V>,
+}
+
+impl<V: Encode + Decode, Q: QueryKind<V>> Item<V, Q> {
+	fn new(storage: Storage, pallet: &str, item: &str, on_empty: fn() -> Q::Query) -> Self {
+		Self { storage, prefix: item_key(pallet, item), on_empty, _value: PhantomData }
+	}
+
+	/// The key of the entry of a map at `suffix`.
+	fn key(&self, suffix: &impl Encode) -> Vec<u8> {
+		let mut key = self.prefix.clone();
+		suffix.encode_to(&mut key);
+		key
+	}
+
+	fn get(&self, key: &[u8]) -> Q::Query {
+		self.storage.get(key).map(Q::from_value).unwrap_or_else(self.on_empty)
+	}
+
+	fn set(&self, key: &[u8], query: Q::Query) {
+		match Q::into_value(query) {
+			Some(value) => self.storage.put(key, &value),
+			None => self.storage.remove(key),
+		}
+	}
+
+	fn try_mutate<R, E>(
+		&self,
+		key: &[u8],
+		f: impl FnOnce(&mut Q::Query) -> Result<R, E>,
+	) -> Result<R, E> {
+		let mut query = self.get(key);
+		let result = f(&mut query)?;
+		self.set(key, query);
+		Ok(result)
+	}
+
+	fn mutate<R>(&self, key: &[u8], f: impl FnOnce(&mut Q::Query) -> R) -> R {
+		let Ok(result) = self.try_mutate(key, |query| Ok::<_, core::convert::Infallible>(f(query)));
+		result
+	}
+
+	/// The entries whose key starts with `prefix`, with the rest of their key decoded as `K`.
+	fn entries<K: Decode>(&self, prefix: &[u8]) -> Vec<(K, V)> {
+		self.storage
+			.pairs_with_prefix(prefix)
+			.into_iter()
+			.map(|(key, value)| {
+				let key = decode_all(&key[prefix.len()..]).expect("storage is not corrupted");
+				(key, decode_all(&value).expect("storage is not corrupted"))
+			})
+			.collect()
+	}
+}
+
+impl<V, Q: QueryKind<V>> Clone for Item<V, Q> {
+	fn clone(&self) -> Self {
+		Self {
+			storage: self.storage.clone(),
+			prefix: self.prefix.clone(),
+			on_empty: self.on_empty,
+			_value: PhantomData,
+		}
+	}
+}
+
+impl<V, Q: QueryKind<V>> core::fmt::Debug for Item<V, Q> {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		f.debug_struct("Item").field("prefix", &self.prefix).finish_non_exhaustive()
+	}
+}
+
+/// A storage item which holds a single value. Usually declared as a field of a pallet with
+/// `#[macros::storage]`.
+pub struct StorageValue<V, Q: QueryKind<V> = OptionQuery>(Item<V, Q>);
+
+impl<V: Encode + Decode, Q: QueryKind<V>> StorageValue<V, Q> {
+	/// The item `item` of `pallet`, which is `on_empty()` until a value is stored.
+	pub fn new(storage: Storage, pallet: &str, item: &str, on_empty: fn() -> Q::Query) -> Self {
+		Self(Item::new(storage, pallet, item, on_empty))
+	}
+
+	pub fn get(&self) -> Q::Query {
+		self.0.get(&self.0.prefix)
+	}
+
+	pub fn exists(&self) -> bool {
+		self.0.storage.get_raw(&self.0.prefix).is_some()
+	}
+
+	pub fn put(&mut self, value: &V) {
+		self.0.storage.put(&self.0.prefix, value);
+	}
+
+	/// Remove the value, so that the item is empty.
+	pub fn kill(&mut self) {
+		self.0.storage.remove(&self.0.prefix);
+	}
+
+	/// Change the value with `f`.
+	pub fn mutate<R>(&mut self, f: impl FnOnce(&mut Q::Query) -> R) -> R {
+		self.0.mutate(&self.0.prefix, f)
+	}
+
+	/// Change the value with `f`, unless it returns an error.
+	pub fn try_mutate<R, E>(&mut self, f: impl FnOnce(&mut Q::Query) -> Result<R, E>) -> Result<R, E> {
+		self.0.try_mutate(&self.0.prefix, f)
+	}
+}
+
+/// A storage item which maps keys of type `K` to values of type `V`. Every entry is stored at its
+/// own key, so only the entries which are used are read.
+pub struct StorageMap<K, V, Q: QueryKind<V> = OptionQuery>(Item<V, Q>, PhantomData<K>);
+
+impl<K: Encode + Decode, V: Encode + Decode, Q: QueryKind<V>> StorageMap<K, V, Q> {
+	/// The item `item` of `pallet`, whose entries are `on_empty()` until a value is stored.
+	pub fn new(storage: Storage, pallet: &str, item: &str, on_empty: fn() -> Q::Query) -> Self {
+		Self(Item::new(storage, pallet, item, on_empty), PhantomData)
+	}
+
+	pub fn get(&self, key: &K) -> Q::Query {
+		self.0.get(&self.0.key(key))
+	}
+
+	pub fn contains_key(&self, key: &K) -> bool {
+		self.0.storage.get_raw(&self.0.key(key)).is_some()
+	}
+
+	pub fn insert(&mut self, key: &K, value: &V) {
+		self.0.storage.put(&self.0.key(key), value);
+	}
+
+	pub fn remove(&mut self, key: &K) {
+		self.0.storage.remove(&self.0.key(key));
+	}
+
+	/// Change the entry at `key` with `f`.
+	pub fn mutate<R>(&mut self, key: &K, f: impl FnOnce(&mut Q::Query) -> R) -> R {
+		self.0.mutate(&self.0.key(key), f)
+	}
+
+	/// Change the entry at `key` with `f`, unless it returns an error.
+	pub fn try_mutate<R, E>(
+		&mut self,
+		key: &K,
+		f: impl FnOnce(&mut Q::Query) -> Result<R, E>,
+	) -> Result<R, E> {
+		self.0.try_mutate(&self.0.key(key), f)
+	}
+
+	/// Every stored entry, in the order of their encoded keys.
+	pub fn iter(&self) -> Vec<(K, V)> {
+		self.0.entries(&self.0.prefix)
+	}
+}
+
+/// A storage item which maps pairs of keys to values. The entries which share their first key
+/// can be iterated over together.
+pub struct StorageDoubleMap<K1, K2, V, Q: QueryKind<V> = OptionQuery>(
+	Item<V, Q>,
+	PhantomData<(K1, K2)>,
+);
+
+impl<K1, K2, V, Q> StorageDoubleMap<K1, K2, V, Q>
+where
+	K1: Encode + Decode,
+	K2: Encode + Decode,
+	V: Encode + Decode,
+	Q: QueryKind<V>,
+{
+	/// The item `item` of `pallet`, whose entries are `on_empty()` until a value is stored.
+	pub fn new(storage: Storage, pallet: &str, item: &str, on_empty: fn() -> Q::Query) -> Self {
+		Self(Item::new(storage, pallet, item, on_empty), PhantomData)
+	}
+
+	pub fn get(&self, key1: &K1, key2: &K2) -> Q::Query {
+		self.0.get(&self.0.key(&(key1, key2)))
+	}
+
+	pub fn contains_key(&self, key1: &K1, key2: &K2) -> bool {
+		self.0.storage.get_raw(&self.0.key(&(key1, key2))).is_some()
+	}
+
+	pub fn insert(&mut self, key1: &K1, key2: &K2, value: &V) {
+		self.0.storage.put(&self.0.key(&(key1, key2)), value);
+	}
+
+	pub fn remove(&mut self, key1: &K1, key2: &K2) {
+		self.0.storage.remove(&self.0.key(&(key1, key2)));
+	}
+
+	/// Change the entry at `(key1, key2)` with `f`.
+	#[allow(dead_code)] // Balance locks, the only double map, are replaced whole.
+	pub fn mutate<R>(&mut self, key1: &K1, key2: &K2, f: impl FnOnce(&mut Q::Query) -> R) -> R {
+		self.0.mutate(&self.0.key(&(key1, key2)), f)
+	}
+
+	/// Change the entry at `(key1, key2)` with `f`, unless it returns an error.
+	#[allow(dead_code)] // Setting a balance lock cannot fail part way.
+	pub fn try_mutate<R, E>(
+		&mut self,
+		key1: &K1,
+		key2: &K2,
+		f: impl FnOnce(&mut Q::Query) -> Result<R, E>,
+	) -> Result<R, E> {
+		self.0.try_mutate(&self.0.key(&(key1, key2)), f)
+	}
+
+	/// The stored entries whose first key is `key1`, in the order of their encoded second keys.
+	pub fn iter_prefix(&self, key1: &K1) -> Vec<(K2, V)> {
+		self.0.entries(&self.0.key(key1))
+	}
+
+	/// Every stored entry, in the order of their encoded keys.
+	#[allow(dead_code)] // Balance locks are only ever read per account.
+	pub fn iter(&self) -> Vec<(K1, K2, V)> {
+		self.0.entries(&self.0.prefix).into_iter().map(|((key1, key2), value)| (key1, key2, value)).collect()
+	}
+}
+
+// The typed storage items can be cloned and printed whatever their keys and values are, so that
+// pallets holding them can derive `Clone` and `Debug`.
+macro_rules! impl_clone_debug {
+	( $name:ident < $( $param:ident ),* > $( , $phantom:tt )? ) => {
+		impl<$( $param, )* V, Q: QueryKind<V>> Clone for $name<$( $param, )* V, Q> {
+			fn clone(&self) -> Self {
+				Self(self.0.clone() $( , $phantom )?)
+			}
+		}
+
+		impl<$( $param, )* V, Q: QueryKind<V>> core::fmt::Debug for $name<$( $param, )* V, Q> {
+			fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+				f.debug_tuple(stringify!($name)).field(&self.0).finish()
+			}
+		}
+	};
+}
+
+impl_clone_debug!(StorageValue<>);
+impl_clone_debug!(StorageMap<K>, PhantomData);
+impl_clone_debug!(StorageDoubleMap<K1, K2>, PhantomData);
+
+#[cfg(test)]
+mod tests {
+	use super::{Backend, FileBackend, Storage, StorageDoubleMap, StorageMap, StorageValue, ValueQuery};
+
+	#[test]
+	fn transactions() {
+		let storage = Storage::in_memory();
+		storage.put(b"a", &1u32);
+
+		let res = storage.with_transaction(|| {
+			storage.put(b"a", &2u32);
+			storage.put(b"b", &3u32);
+			// Nested transactions are rolled back independently.
+			let _ = storage.with_transaction(|| {
+				storage.remove(b"a");
+				Err::<(), _>("failed")
+			});
+			assert_eq!(storage.get::<u32>(b"a"), Some(2));
+			Err::<(), _>("failed")
+		});
+		assert_eq!(res, Err("failed"));
+		assert_eq!(storage.get::<u32>(b"a"), Some(1));
+		assert_eq!(storage.get::<u32>(b"b"), None);
+
+		let root = storage.root();
+		storage.start_transaction();
+		storage.put(b"b", &3u32);
+		storage.rollback_transaction();
+		assert_eq!(storage.root(), root);
+
+		let res = storage.with_transaction(|| {
+			storage.put(b"b", &3u32);
+			Ok::<_, ()>(())
+		});
+		assert_eq!(res, Ok(()));
+		assert_eq!(storage.get::<u32>(b"b"), Some(3));
+		assert_ne!(storage.root(), root);
+	}
+
+	#[test]
+	fn pairs_with_prefix() {
+		let storage = Storage::in_memory();
+		storage.put(b"aa", &1u32);
+		storage.put(b"ab", &2u32);
+		storage.put(b"b", &3u32);
+		storage.flush().unwrap();
+		let root = storage.root();
+
+		storage.start_transaction();
+		storage.remove(b"aa");
+		storage.put(b"ac", &4u32);
+		storage.put(b"a", &5u32);
+		let pairs = storage.pairs_with_prefix(b"a");
+		let keys = pairs.iter().map(|(key, _)| &key[..]).collect::<Vec<_>>();
+		assert_eq!(keys, vec![&b"a"[..], b"ab", b"ac"]);
+		assert_ne!(storage.root(), root);
+
+		// The cached root is dropped along with the changes it was computed from.
+		storage.rollback_transaction();
+		assert_eq!(storage.pairs_with_prefix(b"a").len(), 2);
+		assert_eq!(storage.root(), root);
+	}
+
+	#[test]
+	fn events() {
+		let storage = Storage::in_memory();
+		storage.deposit_event(1u32);
+		storage.deposit_event("a");
+		let _ = storage.with_transaction(|| {
+			storage.deposit_event(2u32);
+			Err::<(), _>("failed")
+		});
+		let _ = storage.with_transaction(|| {
+			storage.deposit_event(3u32);
+			// Events are taken by their type, wherever they were deposited.
+			assert_eq!(storage.take_events::<&str>(), vec!["a"]);
+			Ok::<_, ()>(())
+		});
+		assert_eq!(storage.take_events::<u32>(), vec![1, 3]);
+		assert!(storage.take_events::<u32>().is_empty());
+	}
+
+	#[test]
+	fn file_backend_survives_restart() {
+		let path = std::env::temp_dir().join(format!("storage-test-{}.log", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+
+		let mut backend = FileBackend::open(&path).unwrap();
+		backend.compact_every = 3;
+		let storage = Storage::new(backend);
+		for i in 0..10u32 {
+			storage.put(b"counter", &i);
+			storage.put(&i.to_le_bytes(), &i);
+			storage.remove(&(i.wrapping_sub(1)).to_le_bytes());
+			storage.flush().unwrap();
+		}
+		let root = storage.root();
+		drop(storage);
+
+		// A record which was only partially written is ignored.
+		use std::io::Write;
+		let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+		file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+		drop(file);
+
+		let backend = FileBackend::open(&path).unwrap();
+		assert_eq!(backend.pairs().len(), 2);
+		let storage = Storage::new(backend);
+		assert_eq!(storage.get::<u32>(b"counter"), Some(9));
+		assert_eq!(storage.get::<u32>(&9u32.to_le_bytes()), Some(9));
+		assert_eq!(storage.get::<u32>(&8u32.to_le_bytes()), None);
+		assert_eq!(storage.root(), root);
+
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn file_backend_recovers_from_failed_writes() {
+		use std::io::Write;
+		let path = std::env::temp_dir().join(format!("storage-failure-test-{}.log", std::process::id()));
+		let tmp_path = path.with_extension("compact");
+		let _ = std::fs::remove_file(&path);
+		let change = |key: &[u8], value: u8| [(key.to_vec(), Some(vec![value]))].into();
+
+		let mut backend = FileBackend::open(&path).unwrap();
+		backend.compact_every = 2;
+		backend.commit(change(b"a", 1)).unwrap();
+
+		// A write which fails part way leaves a partial record after the last valid one, and writes
+		// through a read-only handle fail.
+		let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+		file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+		backend.file = std::fs::File::open(&path).unwrap();
+		assert!(backend.commit(change(b"b", 2)).is_err());
+
+		// A failed compaction does not fail the commit, and is retried by the next one.
+		std::fs::create_dir(&tmp_path).unwrap();
+		backend.commit(change(b"c", 3)).unwrap();
+		assert_eq!(backend.commits_since_compaction, 2);
+		// The commit after the failed write is kept on restart.
+		assert_eq!(FileBackend::open(&path).unwrap().pairs().len(), 2);
+		std::fs::remove_dir(&tmp_path).unwrap();
+		backend.commit(change(b"d", 4)).unwrap();
+		assert_eq!(backend.commits_since_compaction, 0);
+		backend.commit(change(b"e", 5)).unwrap();
+		drop(backend);
+
+		let backend = FileBackend::open(&path).unwrap();
+		let keys = backend.pairs().into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+		assert_eq!(keys, vec![b"a".to_vec(), b"c".to_vec(), b"d".to_vec(), b"e".to_vec()]);
+
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn storage_items() {
+		let storage = Storage::in_memory();
+		let mut value = StorageValue::<u32, ValueQuery>::new(storage.clone(), "Test", "Value", || 7);
+		assert_eq!((value.get(), value.exists()), (7, false));
+		value.mutate(|v| *v += 1);
+		assert_eq!((value.get(), value.exists()), (8, true));
+		assert_eq!(value.try_mutate(|v| if *v > 5 { Err("too big") } else { Ok(*v) }), Err("too big"));
+		assert_eq!(value.get(), 8);
+		value.kill();
+		assert_eq!(value.get(), 7);
+
+		let mut map = StorageMap::<u32, String>::new(storage.clone(), "Test", "Map", || None);
+		map.insert(&2, &"two".to_string());
+		map.insert(&1, &"one".to_string());
+		// Setting an entry to `None` removes it.
+		map.mutate(&1, |v| *v = None);
+		map.mutate(&3, |v| *v = Some("three".to_string()));
+		assert!(!map.contains_key(&1));
+		assert_eq!(map.get(&3), Some("three".to_string()));
+		assert_eq!(map.iter(), vec![(2, "two".to_string()), (3, "three".to_string())]);
+
+		// Items with different names never share entries.
+		let mut double = StorageDoubleMap::<u32, u32, u32>::new(storage.clone(), "Test", "Mapp", || None);
+		double.insert(&1, &10, &110);
+		double.insert(&1, &20, &120);
+		double.insert(&2, &10, &210);
+		assert_eq!(double.iter_prefix(&1), vec![(10, 110), (20, 120)]);
+		assert_eq!(double.iter().len(), 3);
+		double.remove(&1, &10);
+		assert_eq!(double.get(&1, &10), None);
+		double.mutate(&2, &20, |v| *v = Some(220));
+		assert_eq!(double.try_mutate(&2, &20, |v| if v.is_some() { Err("taken") } else { Ok(()) }), Err("taken"));
+		assert_eq!(double.try_mutate(&2, &10, |v| v.take().ok_or("empty")), Ok(210));
+		assert_eq!(double.iter(), vec![(1, 20, 120), (2, 20, 220)]);
+		assert_eq!(map.iter().len(), 2);
+	}
+}
@@ -0,0 +1,237 @@
+use core::fmt::Debug;
+use crate::codec::{Decode, Encode};
+use crate::storage::{Storage, StorageValue};
+use crate::support::{DispatchNested, DispatchResult, DispatchResultWithPostInfo, GenesisError, GetDispatchInfo, Origin};
+use crate::system::ensure_signed;
+
+pub trait Config: crate::system::Config {
+	/// The calls of the runtime, which the key can dispatch.
+	type RuntimeCall: Debug + Clone + Encode + Decode + GetDispatchInfo;
+}
+
+/// The events emitted by the sudo pallet.
+#[macros::event(generate_deposit)]
+#[derive(Debug, macros::CloneNoBound, PartialEq)]
+pub enum Event<T: Config> {
+	/// A call dispatched by the key with root privileges returned `sudo_result`.
+	Sudid { sudo_result: DispatchResult },
+	/// The key changed from `old` to `new`.
+	KeyChanged { old: Option<T::AccountId>, new: T::AccountId },
+	/// A call dispatched by the key on behalf of another account returned `sudo_result`.
+	SudoAsDone { sudo_result: DispatchResult },
+}
+
+/// The errors returned by the sudo pallet.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	/// The caller is not the key.
+	RequireSudo,
+}
+
+/// The genesis state of the sudo pallet.
+#[derive(Debug, macros::CloneNoBound, serde::Deserialize)]
+#[serde(bound(deserialize = "T::AccountId: serde::Deserialize<'de>"), deny_unknown_fields)]
+pub struct GenesisConfig<T: Config> {
+	/// The account which can make privileged calls. Nobody can when there is none.
+	pub key: Option<T::AccountId>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { key: None }
+	}
+}
+
+const PALLET: &str = "Sudo";
+
+/// This pallet lets a single account, the key, dispatch any call of the runtime with root
+/// privileges, or on behalf of any other account.
+#[macros::storage]
+#[derive(Debug)]
+pub struct Pallet<T: Config> {
+	/// The storage handle, through which this pallet deposits its events.
+	storage: Storage,
+	/// The account which can make privileged calls.
+	key: StorageValue<T::AccountId>,
+	/// The call made by the key which the runtime has yet to dispatch, with its origin.
+	nested_call: Option<(Origin<T::AccountId>, T::RuntimeCall)>,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Build the genesis state of the pallet.
+	pub fn build_genesis(&mut self, config: &GenesisConfig<T>) -> Result<(), GenesisError> {
+		if let Some(key) = &config.key {
+			self.key.put(key);
+		}
+		Ok(())
+	}
+
+	/// The account which can make privileged calls, if any.
+	pub fn key(&self) -> Option<T::AccountId> {
+		self.key.get()
+	}
+
+	/// Check that `origin` is signed by the key.
+	fn ensure_key(&self, origin: T::RuntimeOrigin) -> DispatchResult {
+		let caller = ensure_signed(origin)?;
+		if self.key() != Some(caller) {
+			return Err(Error::RequireSudo.into())
+		}
+		Ok(())
+	}
+}
+
+// The sudo pallet has no per-block logic.
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {}
+
+// The calls made by the key are dispatched by the runtime, which reports their result back.
+impl<T: Config> DispatchNested<T::RuntimeOrigin, T::RuntimeCall> for Pallet<T> {
+	fn take_nested_call(&mut self) -> Option<(T::RuntimeOrigin, T::RuntimeCall)> {
+		self.nested_call.take().map(|(origin, call)| (origin.into(), call))
+	}
+
+	// Calls made with `sudo_as` are the only ones dispatched with a signed origin.
+	fn on_nested_result(&mut self, origin: T::RuntimeOrigin, result: DispatchResultWithPostInfo) {
+		let sudo_result = result.map(|_| ()).map_err(|error| error.error);
+		let event = match origin.into() {
+			Origin::Signed(_) => Event::SudoAsDone { sudo_result },
+			_ => Event::Sudid { sudo_result },
+		};
+		self.deposit_event(event);
+	}
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Dispatch `call` with root privileges. Can only be called by the key.
+	#[call_index(0)]
+	#[weight(10_000u64.saturating_add(call.get_dispatch_info().weight))]
+	// Calls are boxed, since the calls of the runtime include this one.
+	#[allow(clippy::boxed_local)]
+	pub fn sudo(&mut self, origin: T::RuntimeOrigin, call: Box<T::RuntimeCall>) -> DispatchResult {
+		self.ensure_key(origin)?;
+		self.nested_call = Some((Origin::Root, *call));
+		Ok(())
+	}
+
+	/// Dispatch `call` on behalf of `who`, as if they had signed it. Can only be called by the key.
+	#[call_index(1)]
+	#[weight(10_000u64.saturating_add(call.get_dispatch_info().weight))]
+	// Calls are boxed, since the calls of the runtime include this one.
+	#[allow(clippy::boxed_local)]
+	pub fn sudo_as(&mut self, origin: T::RuntimeOrigin, who: T::AccountId, call: Box<T::RuntimeCall>) -> DispatchResult {
+		self.ensure_key(origin)?;
+		self.nested_call = Some((Origin::Signed(who), *call));
+		Ok(())
+	}
+
+	/// Make `new` the key. Can only be called by the key.
+	#[call_index(2)]
+	#[weight(10_000)]
+	pub fn set_key(&mut self, origin: T::RuntimeOrigin, new: T::AccountId) -> DispatchResult {
+		self.ensure_key(origin)?;
+		let old = self.key();
+		self.key.put(&new);
+		self.deposit_event(Event::KeyChanged { old, new });
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::codec::Encode;
+	use crate::support::{DispatchError, DispatchNested, Origin};
+
+	#[derive(Debug, PartialEq)]
+	struct TestConfig;
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::support::TestSignature<String>;
+		type RuntimeOrigin = Origin<String>;
+		type RuntimeEvent = ();
+		type PalletInfo = ();
+		const BLOCK_WEIGHT_LIMIT: crate::support::Weight = u64::MAX;
+	}
+
+	impl crate::balances::Config for TestConfig {
+		type Balance = u128;
+		const EXISTENTIAL_DEPOSIT: u128 = 1;
+	}
+
+	// The key can only dispatch calls of the balances pallet.
+	impl super::Config for TestConfig {
+		type RuntimeCall = crate::balances::Call<TestConfig>;
+	}
+
+	fn new_sudo() -> super::Pallet<TestConfig> {
+		let mut sudo = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+		sudo.build_genesis(&super::GenesisConfig { key: Some("alice".to_string()) }).unwrap();
+		sudo
+	}
+
+	fn set_balance() -> Box<crate::balances::Call<TestConfig>> {
+		Box::new(crate::balances::Call::force_set_balance { who: "bob".to_string(), amount: 100 })
+	}
+
+	#[test]
+	fn only_the_key_can_make_calls() {
+		let mut sudo = new_sudo();
+		assert_eq!(
+			sudo.sudo(Origin::Signed("bob".to_string()), set_balance()),
+			Err(super::Error::RequireSudo.into())
+		);
+		assert_eq!(sudo.sudo(Origin::Root, set_balance()), Err(DispatchError::BadOrigin));
+		assert!(sudo.take_nested_call().is_none());
+
+		assert_eq!(sudo.set_key(Origin::Signed("alice".to_string()), "bob".to_string()), Ok(()));
+		assert_eq!(sudo.key(), Some("bob".to_string()));
+		assert_eq!(
+			sudo.set_key(Origin::Signed("alice".to_string()), "alice".to_string()),
+			Err(super::Error::RequireSudo.into())
+		);
+		assert_eq!(
+			sudo.take_events(),
+			vec![super::Event::KeyChanged { old: Some("alice".to_string()), new: "bob".to_string() }]
+		);
+	}
+
+	#[test]
+	fn calls_are_handed_to_the_runtime() {
+		let mut sudo = new_sudo();
+		assert_eq!(sudo.sudo(Origin::Signed("alice".to_string()), set_balance()), Ok(()));
+		let (origin, call) = sudo.take_nested_call().unwrap();
+		assert_eq!(origin, Origin::Root);
+		assert_eq!(call.encode(), set_balance().encode());
+		assert!(sudo.take_nested_call().is_none());
+		sudo.on_nested_result(origin, Ok(().into()));
+
+		assert_eq!(sudo.sudo_as(Origin::Signed("alice".to_string()), "bob".to_string(), set_balance()), Ok(()));
+		let (origin, _) = sudo.take_nested_call().unwrap();
+		assert_eq!(origin, Origin::Signed("bob".to_string()));
+		sudo.on_nested_result(origin, Err(DispatchError::BadOrigin.into()));
+		assert_eq!(
+			sudo.take_events(),
+			vec![
+				super::Event::Sudid { sudo_result: Ok(()) },
+				super::Event::SudoAsDone { sudo_result: Err(DispatchError::BadOrigin) },
+			]
+		);
+
+		// A call made on behalf of an account can itself be a sudo call, whose result is reported
+		// before the one of the call which made it.
+		assert_eq!(sudo.sudo_as(Origin::Signed("alice".to_string()), "alice".to_string(), set_balance()), Ok(()));
+		let (outer, _) = sudo.take_nested_call().unwrap();
+		assert_eq!(sudo.sudo(Origin::Signed("alice".to_string()), set_balance()), Ok(()));
+		let (inner, _) = sudo.take_nested_call().unwrap();
+		sudo.on_nested_result(inner, Ok(().into()));
+		sudo.on_nested_result(outer, Ok(().into()));
+		assert_eq!(
+			sudo.take_events(),
+			vec![super::Event::Sudid { sudo_result: Ok(()) }, super::Event::SudoAsDone { sudo_result: Ok(()) }]
+		);
+	}
+}
@@ -1,4 +1,7 @@
+use crate::codec::{Decode, Encode, Error};
+
 /// The most primitive representation of a Blockchain block.
+#[derive(Debug, Clone)]
 pub struct Block<Header, Extrinsic> {
     /// The block header contains metadata about the block.
     pub header: Header,
@@ -6,37 +9,698 @@ pub struct Block<Header, Extrinsic> {
     pub extrinsics: Vec<Extrinsic>,
   }
   
-  /// We are using an extremely simplified header which only contains the current block number.
-  /// On a real blockchain, you would expect to also find:
-  /// - parent block hash
-  /// - state root
-  /// - extrinsics root
-  /// - etc...
+  /// We are using a simplified header which links the block to its parent, and commits to the
+  /// extrinsics in the block and to the state after executing them. On a real blockchain, you would
+  /// also expect to find a digest with consensus information.
+  #[derive(Debug, Clone, PartialEq, Eq)]
   pub struct Header<BlockNumber> {
+    /// The hash of the header of the previous block.
+    pub parent_hash: Hash,
     pub block_number: BlockNumber,
+    /// The hash of the storage of every pallet after executing this block.
+    pub state_root: Hash,
+    /// The hash of all the extrinsics included in this block.
+    pub extrinsics_root: Hash,
   }
   
   /// This is an "extrinsic": literally an external message from outside of the blockchain.
-  /// This simplified version of an extrinsic tells us who is making the call, and which call they are
-  /// making.
-  pub struct Extrinsic<Caller, Call> {
-    pub caller: Caller,
+  /// This simplified version of an extrinsic tells us which call is being made, and, if it is
+  /// signed, who is making it.
+  #[derive(Debug, Clone)]
+  pub struct Extrinsic<Caller, Call, Nonce, BlockNumber, Tip, Signature> {
+    /// `None` for an unsigned extrinsic, which is dispatched with `Origin::None`.
+    pub signature: Option<SignaturePayload<Caller, Nonce, BlockNumber, Tip, Signature>>,
     pub call: Call,
   }
+
+  /// Who signed an extrinsic, and their signature over `signing_payload`.
+  #[derive(Debug, Clone)]
+  pub struct SignaturePayload<Caller, Nonce, BlockNumber, Tip, Signature> {
+    /// The account making the call, which is also the public key the signature is checked against.
+    pub caller: Caller,
+    /// This must be equal to the current nonce of the `caller`. It protects against the same
+    /// extrinsic being replayed.
+    pub nonce: Nonce,
+    /// A block which the `caller` already existed at, whose hash is signed. Since the nonce of an
+    /// account starts over when it is reaped and created again, this protects against the
+    /// extrinsics it signed before being replayed.
+    pub checkpoint: BlockNumber,
+    /// An amount the `caller` pays on top of the fee of the extrinsic.
+    pub tip: Tip,
+    pub signature: Signature,
+  }
+
+  /// A signature which can be verified against a message and the public key of its signer.
+  pub trait Verify {
+    /// The public key of the signer.
+    type Signer;
+
+    /// Returns `true` if `self` is a valid signature of `message` by `signer`.
+    fn verify(&self, message: &[u8], signer: &Self::Signer) -> bool;
+  }
+
+  /// The message signed by the caller of an extrinsic: the encoded call, nonce of the caller, tip,
+  /// genesis hash and hash of the checkpoint. Committing to the genesis hash means a signature
+  /// cannot be reused on another chain.
+  pub fn signing_payload<Call, Nonce, Tip>(
+    call: &Call,
+    nonce: &Nonce,
+    tip: &Tip,
+    genesis_hash: &Hash,
+    checkpoint_hash: &Hash,
+  ) -> Vec<u8>
+  where
+    Call: Encode,
+    Nonce: Encode,
+    Tip: Encode,
+  {
+    (call, nonce, tip, genesis_hash, checkpoint_hash).encode()
+  }
+
+  /// A trivial signature scheme for tests: a signature is valid if it names its signer.
+  #[cfg(test)]
+  #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+  pub struct TestSignature<Signer>(pub Signer);
+
+  #[cfg(test)]
+  impl<Signer: PartialEq> Verify for TestSignature<Signer> {
+    type Signer = Signer;
+
+    fn verify(&self, _message: &[u8], signer: &Self::Signer) -> bool {
+      self.0 == *signer
+    }
+  }
   
+  impl<Header: Encode, Extrinsic: Encode> Encode for Block<Header, Extrinsic> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+      self.header.encode_to(dest);
+      self.extrinsics.encode_to(dest);
+    }
+  }
+
+  impl<Header: Decode, Extrinsic: Decode> Decode for Block<Header, Extrinsic> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+      Ok(Self { header: Decode::decode(input)?, extrinsics: Decode::decode(input)? })
+    }
+  }
+
+  impl<BlockNumber: Encode> Encode for Header<BlockNumber> {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+      self.parent_hash.encode_to(dest);
+      self.block_number.encode_to(dest);
+      self.state_root.encode_to(dest);
+      self.extrinsics_root.encode_to(dest);
+    }
+  }
+
+  impl<BlockNumber: Decode> Decode for Header<BlockNumber> {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+      Ok(Self {
+        parent_hash: Decode::decode(input)?,
+        block_number: Decode::decode(input)?,
+        state_root: Decode::decode(input)?,
+        extrinsics_root: Decode::decode(input)?,
+      })
+    }
+  }
+
+  impl<Caller, Call, Nonce, BlockNumber, Tip, Signature> Encode
+    for Extrinsic<Caller, Call, Nonce, BlockNumber, Tip, Signature>
+  where
+    Caller: Encode,
+    Call: Encode,
+    Nonce: Encode,
+    BlockNumber: Encode,
+    Tip: Encode,
+    Signature: Encode,
+  {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+      self.signature.encode_to(dest);
+      self.call.encode_to(dest);
+    }
+  }
+
+  impl<Caller, Call, Nonce, BlockNumber, Tip, Signature> Decode
+    for Extrinsic<Caller, Call, Nonce, BlockNumber, Tip, Signature>
+  where
+    Caller: Decode,
+    Call: Decode,
+    Nonce: Decode,
+    BlockNumber: Decode,
+    Tip: Decode,
+    Signature: Decode,
+  {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+      Ok(Self { signature: Decode::decode(input)?, call: Decode::decode(input)? })
+    }
+  }
+
+  impl<Caller: Encode, Nonce: Encode, BlockNumber: Encode, Tip: Encode, Signature: Encode> Encode
+    for SignaturePayload<Caller, Nonce, BlockNumber, Tip, Signature>
+  {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+      self.caller.encode_to(dest);
+      self.nonce.encode_to(dest);
+      self.checkpoint.encode_to(dest);
+      self.tip.encode_to(dest);
+      self.signature.encode_to(dest);
+    }
+  }
+
+  impl<Caller: Decode, Nonce: Decode, BlockNumber: Decode, Tip: Decode, Signature: Decode> Decode
+    for SignaturePayload<Caller, Nonce, BlockNumber, Tip, Signature>
+  {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+      Ok(Self {
+        caller: Decode::decode(input)?,
+        nonce: Decode::decode(input)?,
+        checkpoint: Decode::decode(input)?,
+        tip: Decode::decode(input)?,
+        signature: Decode::decode(input)?,
+      })
+    }
+  }
+
   /// The Result type for our runtime. When everything completes successfully, we return `Ok(())`,
-  /// otherwise return a static error message.
-  pub type DispatchResult = Result<(), &'static str>;
+  /// otherwise return a `DispatchError` describing what went wrong.
+  pub type DispatchResult = Result<(), DispatchError>;
+
+  /// The Result type of a call which may report the weight it actually used, both when it
+  /// succeeds and when it fails. Calls returning a `DispatchResult` are converted into this type,
+  /// with no actual weight.
+  pub type DispatchResultWithPostInfo = Result<PostDispatchInfo, DispatchErrorWithPostInfo>;
+
+  /// The reason why a block could not be imported. When this happens, none of the changes made by
+  /// the block are kept.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum BlockError {
+    /// The genesis state of the runtime is not built yet, so there is no block to build on.
+    NoGenesis,
+    /// The block number is not the one following the current block number.
+    InvalidBlockNumber,
+    /// The parent hash does not match the hash of the previous block.
+    InvalidParentHash,
+    /// The extrinsics root does not match the extrinsics in the block.
+    InvalidExtrinsicsRoot,
+    /// The state root does not match the state after executing the block.
+    InvalidStateRoot,
+    /// The nonce of an extrinsic is lower than the nonce of its caller: it was already included.
+    StaleNonce { extrinsic_index: u32 },
+    /// The nonce of an extrinsic is higher than the nonce of its caller: it cannot be included yet.
+    FutureNonce { extrinsic_index: u32 },
+    /// The signature of an extrinsic is not valid for its caller.
+    BadSignature { extrinsic_index: u32 },
+    /// The checkpoint of an extrinsic is not a block of the chain, or its caller did not exist yet
+    /// at that block, which means it may have been signed before the caller was reaped.
+    BadCheckpoint { extrinsic_index: u32 },
+    /// Including an extrinsic would make the weight of the block exceed its limit.
+    ExhaustsResources { extrinsic_index: u32 },
+    /// The caller of an extrinsic cannot pay its fee.
+    CannotPayFees { extrinsic_index: u32 },
+    /// An extrinsic is not signed, and its call does not allow unsigned extrinsics.
+    Unsigned { extrinsic_index: u32 },
+    /// The state after the block could not be written to the storage backend.
+    Storage(std::io::ErrorKind),
+  }
+
+  /// The reason why the genesis state could not be built.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum GenesisError {
+    /// The storage already holds the state of a chain.
+    AlreadyBuilt,
+    /// An account is endowed more than once.
+    DuplicateAccount,
+    /// The same content is claimed more than once.
+    DuplicateClaim,
+    /// The sum of all endowed balances overflows.
+    TotalOverflow,
+    /// An account is endowed with less than the existential deposit.
+    BelowExistentialDeposit,
+    /// Funds are locked in an account which is not endowed.
+    UnknownAccount,
+    /// The genesis state could not be written to the storage backend.
+    Storage(std::io::ErrorKind),
+  }
+
+  /// The reason why a call could not be dispatched.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum DispatchError {
+    /// An error declared by a pallet with `#[macros::error]`.
+    Module(ModuleError),
+    /// An arithmetic operation overflowed or underflowed.
+    Arithmetic(ArithmeticError),
+    /// The origin of the call is not allowed to make it.
+    BadOrigin,
+    /// An account cannot be reaped, because other pallets still depend on it.
+    ConsumerRemaining,
+    /// A pallet cannot depend on an account which does not exist.
+    NoProviders,
+    /// Any other error, with a static description of what went wrong.
+    Other(&'static str),
+  }
+
+  /// An error declared by a pallet.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub struct ModuleError {
+    /// The index of the pallet in the runtime. Pallets do not know their own index, so this is
+    /// filled in by the runtime when it routes a call to the pallet.
+    pub index: u8,
+    /// The index of the variant in the pallet's `Error` enum.
+    pub error: u8,
+    /// The name of the variant in the pallet's `Error` enum.
+    pub message: &'static str,
+  }
+
+  /// The arithmetic errors which can occur while dispatching a call.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum ArithmeticError {
+    /// The result of an operation is lower than the minimum value of its type.
+    Underflow,
+    /// The result of an operation is higher than the maximum value of its type.
+    Overflow,
+  }
+
+  impl DispatchError {
+    /// Set the pallet index of a `Module` error which has none yet. An error which already has one
+    /// was tagged by the pallet which returned it, and any other error is returned unchanged.
+    pub fn with_pallet_index(self, index: u8) -> Self {
+      match self {
+        DispatchError::Module(error) if error.index == 0 => DispatchError::Module(ModuleError { index, ..error }),
+        other => other,
+      }
+    }
+
+    /// Report that the call failed after using `actual_weight`.
+    pub fn with_weight(self, actual_weight: Weight) -> DispatchErrorWithPostInfo {
+      DispatchErrorWithPostInfo {
+        post_info: PostDispatchInfo { actual_weight: Some(actual_weight) },
+        error: self,
+      }
+    }
+  }
+
+  /// A `DispatchError`, along with the weight the failed call actually used.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub struct DispatchErrorWithPostInfo {
+    pub post_info: PostDispatchInfo,
+    pub error: DispatchError,
+  }
+
+  impl DispatchErrorWithPostInfo {
+    /// Set the pallet index of the error, like `DispatchError::with_pallet_index`.
+    pub fn with_pallet_index(self, index: u8) -> Self {
+      Self { error: self.error.with_pallet_index(index), ..self }
+    }
+  }
+
+  impl<E: Into<DispatchError>> From<E> for DispatchErrorWithPostInfo {
+    fn from(error: E) -> Self {
+      Self { post_info: PostDispatchInfo::default(), error: error.into() }
+    }
+  }
+
+  impl From<ArithmeticError> for DispatchError {
+    fn from(error: ArithmeticError) -> Self {
+      DispatchError::Arithmetic(error)
+    }
+  }
+
+  impl From<&'static str> for DispatchError {
+    fn from(error: &'static str) -> Self {
+      DispatchError::Other(error)
+    }
+  }
   
+  /// The origin of a call: who, if anyone, is making it.
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub enum Origin<AccountId> {
+    /// The call is made with privileged, administrative rights.
+    Root,
+    /// The call is made by, and signed by, an account.
+    Signed(AccountId),
+    /// The call is made by no one in particular, for example an unsigned extrinsic.
+    None,
+  }
+
   /// A trait which allows us to dispatch an incoming extrinsic to the appropriate state transition
   /// function call.
   pub trait Dispatch {
-    /// The type used to identify the caller of the function.
-    type Caller;
+    /// The type used to identify the origin of the function call.
+    type Origin;
     /// The state transition function call the caller is trying to access.
     type Call;
   
-    /// This function takes a `caller` and the `call` they want to make, and returns a `Result`
+    /// This function takes the `origin` and the `call` they want to make, and returns a `Result`
     /// based on the outcome of that function call.
-    fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> DispatchResult;
-  }
\ No newline at end of file
+    fn dispatch(&mut self, origin: Self::Origin, call: Self::Call) -> DispatchResultWithPostInfo;
+  }
+
+  /// Calls which a pallet dispatches on behalf of one of its own calls, like `sudo` does. A pallet
+  /// cannot reach the other pallets of the runtime, so it hands such calls over to the runtime,
+  /// which dispatches them once the call of the pallet succeeds, and reports their result back.
+  /// Every pallet implements it; by default, a pallet hands over no calls.
+  pub trait DispatchNested<Origin, Call> {
+    /// The next call to dispatch on behalf of the pallet, with the origin to dispatch it with.
+    /// When the call of the pallet fails, the runtime takes the calls it handed over and discards
+    /// them, so taking a call must not change anything else.
+    fn take_nested_call(&mut self) -> Option<(Origin, Call)> {
+      None
+    }
+
+    /// Called with the origin and the result of a call returned by `take_nested_call`, once it is
+    /// dispatched. The changes made by a nested call which fails are rolled back, but the call of
+    /// the pallet still succeeds.
+    fn on_nested_result(&mut self, _origin: Origin, _result: DispatchResultWithPostInfo) {}
+  }
+
+  /// A pallet whose `Config` names another pallet of the runtime `P`, like the currency it reserves
+  /// deposits in. The runtime lists the pallets it uses in a `#[uses(..)]` attribute on its field,
+  /// and wires a new handle to each of them into it before dispatching each of its calls. Pallets
+  /// keep their state in the storage, so the handle sees the same state as the runtime's own `P`.
+  pub trait UsesPallet<P> {
+    /// Keep `pallet` to use while dispatching the next call.
+    fn wire(&mut self, pallet: P);
+  }
+
+  /// How deeply calls can be nested in one another, like the calls made through `sudo`. Deeper
+  /// calls cannot be decoded.
+  pub const MAX_CALL_DEPTH: u32 = 16;
+
+  /// Tells the index of every pallet of the runtime, which the runtime implements. A pallet which
+  /// returns errors to another pallet tags them with its own index, since the runtime only knows the
+  /// pallet whose call it dispatched. `()` knows no pallet, for pallets tested on their own.
+  pub trait PalletInfo {
+    /// The index of the pallet of type `P`, if it is part of the runtime.
+    fn index<P: 'static>() -> Option<u8>;
+  }
+
+  impl PalletInfo for () {
+    fn index<P: 'static>() -> Option<u8> {
+      None
+    }
+  }
+
+  /// An abstract measure of the work done by a call. Every block can only do a limited amount of
+  /// work, configured by `system::Config::BLOCK_WEIGHT_LIMIT`.
+  pub type Weight = u64;
+
+  /// Information about a call which is known before dispatching it.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub struct DispatchInfo {
+    /// The weight of the call.
+    pub weight: Weight,
+    /// Whether an unsigned extrinsic may make the call. Unsigned extrinsics pay no fee, so a call
+    /// only allows them with an `#[allow_unsigned]` attribute.
+    pub allow_unsigned: bool,
+  }
+
+  /// A trait for calls which can tell their `DispatchInfo` before being dispatched.
+  pub trait GetDispatchInfo {
+    fn get_dispatch_info(&self) -> DispatchInfo;
+  }
+
+  /// Logic which a pallet runs in every block, outside of any extrinsic. The runtime calls the
+  /// hooks of every pallet in the order they are declared. Each hook does nothing by default.
+  pub trait Hooks<BlockNumber> {
+    /// Called at the start of block `n`, before any extrinsic is applied. Returns the weight it
+    /// used, which counts towards the weight of the block even if it exceeds the limit.
+    fn on_initialize(&mut self, _n: BlockNumber) -> Weight {
+      0
+    }
+
+    /// Called once every extrinsic of the block is applied, with the weight which is left in the
+    /// block. Returns the weight it used, which should not exceed `remaining_weight`.
+    fn on_idle(&mut self, _remaining_weight: Weight) -> Weight {
+      0
+    }
+
+    /// Called at the end of block `n`, after `on_idle`.
+    fn on_finalize(&mut self, _n: BlockNumber) {}
+
+    /// Check the invariants of the state of the pallet. Only called in debug builds, after every
+    /// block is executed.
+    fn try_state(&self) -> Result<(), &'static str> {
+      Ok(())
+    }
+  }
+
+  /// An instance of a pallet which can be included in a runtime more than once. Instantiable
+  /// pallets take an instance type parameter `I: Instance`, which defaults to `()`.
+  pub trait Instance: 'static {
+    /// Prefixes the name of the pallet in the keys of its storage, so that every instance has its
+    /// own storage. Empty for the default instance.
+    const PREFIX: &'static str;
+  }
+
+  impl Instance for () {
+    const PREFIX: &'static str = "";
+  }
+
+  /// A second instance of a pallet.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub struct Instance1;
+
+  impl Instance for Instance1 {
+    const PREFIX: &'static str = "Instance1";
+  }
+
+  /// Information about a call which is only known after dispatching it.
+  #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+  pub struct PostDispatchInfo {
+    /// The weight the call actually used, or `None` if it used all of its declared weight.
+    pub actual_weight: Option<Weight>,
+  }
+
+  impl PostDispatchInfo {
+    /// The weight actually used by a call declared with `info`. A call can never use more than its
+    /// declared weight.
+    pub fn calc_actual_weight(&self, info: &DispatchInfo) -> Weight {
+      self.actual_weight.map_or(info.weight, |weight| weight.min(info.weight))
+    }
+  }
+
+  impl From<()> for PostDispatchInfo {
+    fn from(_: ()) -> Self {
+      Self::default()
+    }
+  }
+
+  /// What is known about the fee of a signed extrinsic once its call is dispatched.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub struct FeeSettlement<Balance> {
+    /// Information about the call, known before dispatching it.
+    pub info: DispatchInfo,
+    /// Information about the call, known after dispatching it.
+    pub post_info: PostDispatchInfo,
+    /// The length of the extrinsic in bytes.
+    pub len: u32,
+    /// The tip paid on top of the fee.
+    pub tip: Balance,
+    /// The amount withdrawn from the caller before dispatching the call.
+    pub paid: Balance,
+  }
+
+  /// Charges the fees of signed extrinsics. It is implemented by the runtime, usually by
+  /// delegating to the transaction payment pallet.
+  pub trait ChargeTransaction {
+    type AccountId;
+    type Balance;
+
+    /// Withdraw the fee of an extrinsic of `len` bytes, whose call has `info`, and its `tip` from
+    /// `who`, before dispatching the call. Returns the amount withdrawn, or `None` if `who` cannot
+    /// pay it.
+    fn withdraw_fee(
+      &mut self,
+      who: &Self::AccountId,
+      info: &DispatchInfo,
+      len: u32,
+      tip: Self::Balance,
+    ) -> Option<Self::Balance>;
+
+    /// Whether `who` can pay the fee `withdraw_fee` would withdraw, without withdrawing it.
+    fn can_pay_fee(&self, who: &Self::AccountId, info: &DispatchInfo, len: u32, tip: Self::Balance) -> bool;
+
+    /// Once the call is dispatched, refund `who` for the weight it did not use out of the amount
+    /// it paid, and move the rest of the fee to its destination.
+    fn settle_fee(&mut self, who: &Self::AccountId, settlement: &FeeSettlement<Self::Balance>);
+  }
+
+  /// Identifies a lock on the balance of an account, so that the pallet which set it can change or
+  /// remove it later.
+  pub type LockIdentifier = [u8; 8];
+
+  /// The uses of funds which a lock forbids.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+  pub enum WithdrawReasons {
+    /// Paying the fees of extrinsics.
+    Fee,
+    /// Transferring and reserving funds.
+    Transfer,
+    /// Any use of funds.
+    All,
+  }
+
+  impl WithdrawReasons {
+    /// Whether a lock forbidding `self` applies to funds withdrawn for `reasons`.
+    pub fn intersects(self, reasons: WithdrawReasons) -> bool {
+      self == WithdrawReasons::All || reasons == WithdrawReasons::All || self == reasons
+    }
+
+    /// The reasons which forbid the uses forbidden by either `self` or `other`.
+    pub fn union(self, other: WithdrawReasons) -> WithdrawReasons {
+      if self == other { self } else { WithdrawReasons::All }
+    }
+  }
+
+  impl Encode for WithdrawReasons {
+    fn encode_to(&self, dest: &mut Vec<u8>) {
+      dest.push(*self as u8);
+    }
+  }
+
+  impl Decode for WithdrawReasons {
+    fn decode(input: &mut &[u8]) -> Result<Self, Error> {
+      match u8::decode(input)? {
+        0 => Ok(WithdrawReasons::Fee),
+        1 => Ok(WithdrawReasons::Transfer),
+        2 => Ok(WithdrawReasons::All),
+        _ => Err(Error::InvalidVariant),
+      }
+    }
+  }
+
+  /// Whether an operation which takes funds out of an account may reap it.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum ExistenceRequirement {
+    /// The operation fails rather than leave the account below the existential deposit.
+    KeepAlive,
+    /// The account is reaped if it drops below the existential deposit, and its dust burned.
+    AllowDeath,
+  }
+
+  /// Where reserved funds are moved to by `ReservableCurrency::repatriate_reserved`.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum BalanceStatus {
+    /// To the free balance of the beneficiary, which can spend them.
+    Free,
+    /// To the reserved balance of the beneficiary.
+    Reserved,
+  }
+
+  /// A currency held by accounts, like the one of the balances pallet. Pallets which use funds name
+  /// a currency in their `Config`, rather than the pallet which implements it.
+  pub trait Currency {
+    type AccountId;
+    type Balance: Copy + Ord + core::fmt::Debug + Encode + Decode;
+
+    /// The funds of `who` which can be spent, as far as the locks on the account allow.
+    fn free_balance(&self, who: &Self::AccountId) -> Self::Balance;
+
+    /// The sum of the balances of all accounts.
+    fn total_issuance(&self) -> Self::Balance;
+
+    /// The smallest balance an account can hold.
+    fn minimum_balance(&self) -> Self::Balance;
+
+    /// Move `amount` from the free balance of `from` to `to`. `existence` tells whether this may
+    /// reap `from`.
+    fn transfer(
+      &mut self,
+      from: &Self::AccountId,
+      to: &Self::AccountId,
+      amount: Self::Balance,
+      existence: ExistenceRequirement,
+    ) -> DispatchResult;
+  }
+
+  /// A currency in which funds can be set aside, for example as a deposit. Reserved funds still
+  /// belong to their account, but cannot be spent until they are unreserved.
+  pub trait ReservableCurrency: Currency {
+    /// The funds of `who` which are reserved.
+    fn reserved_balance(&self, who: &Self::AccountId) -> Self::Balance;
+
+    /// Whether `who` can reserve `amount` out of their free balance.
+    fn can_reserve(&self, who: &Self::AccountId, amount: Self::Balance) -> bool;
+
+    /// Move `amount` from the free to the reserved balance of `who`. Fails if the free balance is
+    /// too low, or locked.
+    fn reserve(&mut self, who: &Self::AccountId, amount: Self::Balance) -> DispatchResult;
+
+    /// Move up to `amount` from the reserved to the free balance of `who`. Returns the part of
+    /// `amount` which was not reserved, and so could not be unreserved.
+    fn unreserve(&mut self, who: &Self::AccountId, amount: Self::Balance) -> Self::Balance;
+
+    /// Move up to `amount` from the reserved balance of `slashed` to the balance of `beneficiary`
+    /// with the given `status`. Returns the part of `amount` which was not reserved, and so could
+    /// not be moved.
+    fn repatriate_reserved(
+      &mut self,
+      slashed: &Self::AccountId,
+      beneficiary: &Self::AccountId,
+      amount: Self::Balance,
+      status: BalanceStatus,
+    ) -> Result<Self::Balance, DispatchError>;
+  }
+
+  /// A currency in which the free balance of an account can be locked. A lock keeps the free balance
+  /// from dropping below its amount for the uses it forbids, without setting the funds aside:
+  /// funds can be locked by several locks at once, and the largest one applies.
+  pub trait LockableCurrency: Currency {
+    /// Lock `amount` of the free balance of `who` for `reasons` under `id`, replacing any lock
+    /// with the same `id`. An account cannot be reaped while it has locks, so this fails if the
+    /// account does not exist.
+    fn set_lock(
+      &mut self,
+      id: LockIdentifier,
+      who: &Self::AccountId,
+      amount: Self::Balance,
+      reasons: WithdrawReasons,
+    ) -> DispatchResult;
+
+    /// Like `set_lock`, but the lock with the same `id`, if any, is only ever made larger: it keeps
+    /// the largest of the amounts, and forbids the uses forbidden by either.
+    fn extend_lock(
+      &mut self,
+      id: LockIdentifier,
+      who: &Self::AccountId,
+      amount: Self::Balance,
+      reasons: WithdrawReasons,
+    ) -> DispatchResult;
+
+    /// Remove the lock `id` from the balance of `who`.
+    fn remove_lock(&mut self, id: LockIdentifier, who: &Self::AccountId);
+  }
+
+  /// The output of our hashing function.
+  pub type Hash = [u8; 32];
+
+  /// Hash `data` with the 256-bit variant of Blake2b.
+  pub fn blake2_256(data: &[u8]) -> Hash {
+    use blake2::{digest::consts::U32, Blake2b, Digest};
+    Blake2b::<U32>::digest(data).into()
+  }
+
+  #[cfg(test)]
+  mod tests {
+    use super::{DispatchError, ModuleError};
+
+    #[test]
+    fn with_pallet_index_only_changes_module_errors() {
+      let error = DispatchError::Module(ModuleError { index: 0, error: 1, message: "Oops" });
+      assert_eq!(
+        error.with_pallet_index(2),
+        DispatchError::Module(ModuleError { index: 2, error: 1, message: "Oops" })
+      );
+      assert_eq!(DispatchError::BadOrigin.with_pallet_index(2), DispatchError::BadOrigin);
+      // The index of the pallet which returned the error is kept.
+      let error = DispatchError::Module(ModuleError { index: 1, error: 1, message: "Oops" });
+      assert_eq!(error.with_pallet_index(2), error);
+    }
+
+    #[test]
+    fn test_signature_names_its_signer() {
+      use super::{TestSignature, Verify};
+      assert!(TestSignature("alice").verify(b"hello", &"alice"));
+      assert!(!TestSignature("alice").verify(b"hello", &"bob"));
+    }
+  }
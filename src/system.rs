@@ -1,43 +1,332 @@
-use std::collections::BTreeMap;
+use core::fmt::Debug;
 use num::traits::{Zero, One};
 use std::ops::AddAssign;
+use crate::codec::{Decode, Encode};
+use crate::storage::{Storage, StorageMap, StorageValue, ValueQuery};
+use crate::support::{self, DispatchError, DispatchResult, GenesisError, Origin, Verify, Weight};
 
-pub trait Config {
-	type AccountId: Ord + Clone;
-	type BlockNumber: Zero + One + AddAssign + Copy;
-	type Nonce: Zero + One + Copy;
+// Configurations are types without lifetimes, so that the events of pallets can be kept in storage.
+pub trait Config: 'static {
+	type AccountId: Debug + Ord + Clone + Encode + Decode;
+	type BlockNumber: Zero + One + AddAssign + Copy + Ord + Encode + Decode;
+	type Nonce: Zero + One + Copy + Ord + Encode + Decode;
+	/// The signature scheme used to sign extrinsics. The signer is the account making the call.
+	type Signature: Verify<Signer = Self::AccountId>;
+	/// The origin type passed to every dispatchable call.
+	type RuntimeOrigin: From<Origin<Self::AccountId>> + Into<Origin<Self::AccountId>>;
+	/// The aggregated event type of the runtime, which wraps the `Event` of every pallet.
+	type RuntimeEvent;
+	/// The indices of the pallets of the runtime.
+	type PalletInfo: support::PalletInfo;
+	/// The maximum total weight of the extrinsics in a block.
+	const BLOCK_WEIGHT_LIMIT: Weight;
 }
 
+/// The events emitted by the system pallet.
+#[macros::event]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+	/// An extrinsic completed successfully.
+	ExtrinsicSuccess,
+	/// An extrinsic failed, and its changes to storage were rolled back.
+	ExtrinsicFailed { error: DispatchError },
+}
+
+/// Ensure that the origin is signed, and return the account which signed it.
+pub fn ensure_signed<AccountId>(
+	origin: impl Into<Origin<AccountId>>,
+) -> Result<AccountId, DispatchError> {
+	match origin.into() {
+		Origin::Signed(who) => Ok(who),
+		_ => Err(DispatchError::BadOrigin),
+	}
+}
+
+/// Ensure that the origin is root.
+pub fn ensure_root<AccountId>(origin: impl Into<Origin<AccountId>>) -> Result<(), DispatchError> {
+	match origin.into() {
+		Origin::Root => Ok(()),
+		_ => Err(DispatchError::BadOrigin),
+	}
+}
+
+/// The part of a block during which an event was emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+	/// While running the `on_initialize` hook of every pallet.
+	Initialization,
+	/// While applying the extrinsic at this index.
+	ApplyExtrinsic(u32),
+	/// While running the `on_idle` and `on_finalize` hooks of every pallet.
+	Finalization,
+}
+
+/// An event emitted during the current block, along with the phase of the block which emitted it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventRecord<E> {
+	pub phase: Phase,
+	pub event: E,
+}
+
+/// What the system pallet knows about an account. An account exists while at least one pallet
+/// provides for it, like the balances pallet does while it holds the existential deposit. Once none
+/// does, the account is reaped, and all of it is removed. Its nonce starts over if it is funded
+/// again, but the extrinsics it signed before cannot be replayed: their checkpoint is older than
+/// the block in which it was created again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountInfo<Nonce, BlockNumber> {
+	/// The number of extrinsics signed by the account.
+	pub nonce: Nonce,
+	/// The number of the block in which the account was created.
+	pub created: BlockNumber,
+	/// The number of pallets which depend on the account, like the balances pallet while it holds
+	/// locks on its balance. The account cannot be reaped while it has any.
+	pub consumers: u32,
+	/// The number of pallets which provide for the account.
+	pub providers: u32,
+}
+
+impl<Nonce: Zero, BlockNumber: Zero> Default for AccountInfo<Nonce, BlockNumber> {
+	fn default() -> Self {
+		Self { nonce: Nonce::zero(), created: BlockNumber::zero(), consumers: 0, providers: 0 }
+	}
+}
+
+impl<Nonce: Encode, BlockNumber: Encode> Encode for AccountInfo<Nonce, BlockNumber> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.nonce.encode_to(dest);
+		self.created.encode_to(dest);
+		self.consumers.encode_to(dest);
+		self.providers.encode_to(dest);
+	}
+}
+
+impl<Nonce: Decode, BlockNumber: Decode> Decode for AccountInfo<Nonce, BlockNumber> {
+	fn decode(input: &mut &[u8]) -> Result<Self, crate::codec::Error> {
+		Ok(Self {
+			nonce: Decode::decode(input)?,
+			created: Decode::decode(input)?,
+			consumers: Decode::decode(input)?,
+			providers: Decode::decode(input)?,
+		})
+	}
+}
+
+/// The genesis state of the system pallet.
+#[derive(Debug, macros::CloneNoBound, serde::Deserialize)]
+#[serde(bound(deserialize = "T::BlockNumber: serde::Deserialize<'de>"), deny_unknown_fields)]
+pub struct GenesisConfig<T: Config> {
+	/// The number of the genesis block. The first block built on top of it has the next number.
+	pub block_number: T::BlockNumber,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self { block_number: T::BlockNumber::zero() }
+	}
+}
+
+const PALLET: &str = "System";
+
+#[macros::storage]
 #[derive(Debug)]
 pub struct Pallet <T: Config> {
-    block_number: T::BlockNumber,
-    nonce: BTreeMap<T::AccountId, T::Nonce>,
+    /// The storage handle shared by every pallet of the runtime.
+    storage: Storage,
+    /// The number of the current block.
+    #[default(T::BlockNumber::zero())]
+    block_number: StorageValue<T::BlockNumber, ValueQuery>,
+    /// The total weight of the extrinsics applied in the current block.
+    block_weight: StorageValue<Weight, ValueQuery>,
+    /// The hash of the genesis block, which identifies the chain.
+    genesis_hash: StorageValue<support::Hash>,
+    /// The hash of every imported block.
+    block_hash: StorageMap<T::BlockNumber, support::Hash>,
+    /// The nonce, creation block and reference counts of every account.
+    #[default(AccountInfo::default())]
+    account: StorageMap<T::AccountId, AccountInfo<T::Nonce, T::BlockNumber>, ValueQuery>,
+    /// The events deposited during the current block. Cleared at the start of every block.
+    events: Vec<EventRecord<T::RuntimeEvent>>,
 }
 
 impl<T: Config> Pallet<T> {
-    pub fn new() -> Self {
-        Self {
-            block_number: T::BlockNumber::zero(),
-            nonce: BTreeMap::new(),
-        }
+    /// The storage handle shared by every pallet of the runtime.
+    pub fn storage(&self) -> &Storage {
+        &self.storage
     }
 
     pub fn block_number(&self) -> T::BlockNumber {
-        self.block_number
+        self.block_number.get()
     }
 
     pub fn inc_block_number(&mut self) {
-        self.block_number += T::BlockNumber::one();
+        self.block_number.mutate(|block_number| *block_number += T::BlockNumber::one());
+    }
+
+    /// The total weight of the extrinsics applied in the current block.
+    pub fn block_weight(&self) -> Weight {
+        self.block_weight.get()
+    }
+
+    /// Add `weight` to the weight of the current block, unless that would exceed
+    /// `T::BLOCK_WEIGHT_LIMIT`. Returns whether the weight was added.
+    pub fn consume_weight(&mut self, weight: Weight) -> bool {
+        self.block_weight
+            .try_mutate(|total| match total.checked_add(weight) {
+                Some(new_total) if new_total <= T::BLOCK_WEIGHT_LIMIT => {
+                    *total = new_total;
+                    Ok(())
+                },
+                _ => Err(()),
+            })
+            .is_ok()
+    }
+
+    /// Add `weight` to the weight of the current block, even if that exceeds
+    /// `T::BLOCK_WEIGHT_LIMIT`. Used for work which every block must do, such as hooks.
+    pub fn register_extra_weight(&mut self, weight: Weight) {
+        self.block_weight.mutate(|total| *total = total.saturating_add(weight));
+    }
+
+    /// The weight which can still be consumed in the current block.
+    pub fn remaining_weight(&self) -> Weight {
+        T::BLOCK_WEIGHT_LIMIT.saturating_sub(self.block_weight())
+    }
+
+    /// Give back `weight` which was consumed, but not used, by a call.
+    pub fn refund_weight(&mut self, weight: Weight) {
+        self.block_weight.mutate(|total| *total = total.saturating_sub(weight));
+    }
+
+    /// Start counting the weight of a new block.
+    pub fn reset_block_weight(&mut self) {
+        self.block_weight.kill();
     }
 
     pub fn inc_nonce(&mut self, who: &T::AccountId) {
-        let nonce: T::Nonce = *self.nonce.get(who).unwrap_or(&T::Nonce::zero());
-        let new_nonce = nonce + T::Nonce::one();
-        self.nonce.insert(who.clone(), new_nonce);
+        self.account.mutate(who, |account| account.nonce = account.nonce + T::Nonce::one());
     }
 
     pub fn nonce(&self, who: &T::AccountId) -> T::Nonce {
-        *self.nonce.get(who).unwrap_or(&T::Nonce::zero())
+        self.account.get(who).nonce
+    }
+
+    /// The number of pallets which provide for `who`.
+    pub fn providers(&self, who: &T::AccountId) -> u32 {
+        self.account.get(who).providers
+    }
+
+    /// The number of pallets which depend on `who`.
+    pub fn consumers(&self, who: &T::AccountId) -> u32 {
+        self.account.get(who).consumers
+    }
+
+    /// The number of the block in which `who` was created, if it exists.
+    pub fn created(&self, who: &T::AccountId) -> Option<T::BlockNumber> {
+        let account = self.account.get(who);
+        (account.providers > 0).then_some(account.created)
+    }
+
+    /// Record that a pallet provides for `who`, which creates the account if it did not exist.
+    pub fn inc_providers(&mut self, who: &T::AccountId) {
+        let block_number = self.block_number();
+        self.account.mutate(who, |account| {
+            if account.providers == 0 {
+                account.created = block_number;
+            }
+            account.providers += 1;
+        });
+    }
+
+    /// Whether a pallet can stop providing for `who`: the last provider of an account cannot while
+    /// it has consumers.
+    pub fn can_dec_providers(&self, who: &T::AccountId) -> bool {
+        self.consumers(who) == 0 || self.providers(who) > 1
+    }
+
+    /// Record that a pallet no longer provides for `who`. Once none does, the account is reaped,
+    /// along with its nonce. Fails if the account has consumers which would be left without it.
+    pub fn dec_providers(&mut self, who: &T::AccountId) -> DispatchResult {
+        if !self.can_dec_providers(who) {
+            return Err(DispatchError::ConsumerRemaining)
+        }
+        let mut account = self.account.get(who);
+        match account.providers {
+            0 => {},
+            1 => self.account.remove(who),
+            _ => {
+                account.providers -= 1;
+                self.account.insert(who, &account);
+            },
+        }
+        Ok(())
+    }
+
+    /// Record that a pallet depends on `who`, which then cannot be reaped until it stops. Fails if
+    /// the account does not exist.
+    pub fn inc_consumers(&mut self, who: &T::AccountId) -> DispatchResult {
+        self.account.try_mutate(who, |account| {
+            if account.providers == 0 {
+                return Err(DispatchError::NoProviders)
+            }
+            account.consumers += 1;
+            Ok(())
+        })
+    }
+
+    /// Record that a pallet no longer depends on `who`.
+    pub fn dec_consumers(&mut self, who: &T::AccountId) {
+        if self.account.contains_key(who) {
+            self.account.mutate(who, |account| account.consumers = account.consumers.saturating_sub(1));
+        }
+    }
+
+    /// Build the genesis state of the pallet. Fails if the storage already holds a chain.
+    pub fn build_genesis(&mut self, config: &GenesisConfig<T>) -> Result<(), GenesisError> {
+        if self.genesis_hash.exists() {
+            return Err(GenesisError::AlreadyBuilt)
+        }
+        self.block_number.put(&config.block_number);
+        Ok(())
+    }
+
+    /// The hash of the genesis block, which identifies the chain. `None` until the genesis state
+    /// is built.
+    pub fn genesis_hash(&self) -> Option<support::Hash> {
+        self.genesis_hash.get()
+    }
+
+    /// Store the hash of the genesis block, once the rest of the genesis state is built.
+    pub fn set_genesis_hash(&mut self, hash: support::Hash) {
+        self.genesis_hash.put(&hash);
+        self.set_block_hash(self.block_number(), hash);
+    }
+
+    /// The hash of the block with number `block_number`, if it has been imported.
+    pub fn block_hash(&self, block_number: &T::BlockNumber) -> Option<support::Hash> {
+        self.block_hash.get(block_number)
+    }
+
+    pub fn set_block_hash(&mut self, block_number: T::BlockNumber, hash: support::Hash) {
+        self.block_hash.insert(&block_number, &hash);
+    }
+
+    pub fn deposit_event(&mut self, phase: Phase, event: T::RuntimeEvent) {
+        self.events.push(EventRecord { phase, event });
+    }
+
+    pub fn events(&self) -> &[EventRecord<T::RuntimeEvent>] {
+        &self.events
+    }
+
+    pub fn reset_events(&mut self) {
+        self.events.clear();
+    }
+
+    /// Replace the events of the current block with `events`, like when the changes which
+    /// emitted the current ones are discarded.
+    pub fn set_events(&mut self, events: Vec<EventRecord<T::RuntimeEvent>>) {
+        self.events = events;
     }
 }
 
@@ -50,15 +339,85 @@ mod test {
             type AccountId = String;
             type BlockNumber = u32;
             type Nonce = u32;
+            type Signature = crate::support::TestSignature<String>;
+            type RuntimeOrigin = crate::support::Origin<String>;
+            type RuntimeEvent = super::Event;
+            type PalletInfo = ();
+            const BLOCK_WEIGHT_LIMIT: crate::support::Weight = 100;
         }
 
-		let mut system = super::Pallet::<TestConfig>::new();
+		let mut system = super::Pallet::<TestConfig>::new(crate::storage::Storage::in_memory());
+
+        system.build_genesis(&super::GenesisConfig { block_number: 5 }).unwrap();
+        system.set_genesis_hash([5; 32]);
+        assert_eq!(
+            system.build_genesis(&Default::default()),
+            Err(crate::support::GenesisError::AlreadyBuilt)
+        );
+        assert_eq!(system.genesis_hash(), Some([5; 32]));
+        assert_eq!(system.block_hash(&5), Some([5; 32]));
 
         system.inc_block_number();
         system.inc_nonce(&"alice".to_string());
 
-        assert_eq!(system.block_number(), 1);
+        assert_eq!(system.block_number(), 6);
         assert_eq!(system.nonce(&"alice".to_string()), 1);
         assert_eq!(system.nonce(&"bob".to_string()), 0);
+
+        assert_eq!(system.block_hash(&6), None);
+        system.set_block_hash(6, [6; 32]);
+        assert_eq!(system.block_hash(&6), Some([6; 32]));
+
+        assert!(system.consume_weight(60));
+        assert!(!system.consume_weight(41));
+        assert!(system.consume_weight(40));
+        assert_eq!(system.block_weight(), 100);
+        system.refund_weight(30);
+        assert_eq!(system.block_weight(), 70);
+        system.register_extra_weight(50);
+        assert_eq!(system.block_weight(), 120);
+        assert_eq!(system.remaining_weight(), 0);
+        system.reset_block_weight();
+        assert_eq!(system.block_weight(), 0);
+        assert_eq!(system.remaining_weight(), 100);
+
+        let phase = super::Phase::ApplyExtrinsic(0);
+        system.deposit_event(phase, super::Event::ExtrinsicSuccess);
+        assert_eq!(system.events(), &[super::EventRecord { phase, event: super::Event::ExtrinsicSuccess }]);
+        system.reset_events();
+        assert!(system.events().is_empty());
+
+        // An account lives as long as something provides for it, and consumers keep it alive.
+        let alice = "alice".to_string();
+        assert_eq!(system.inc_consumers(&alice), Err(crate::support::DispatchError::NoProviders));
+        assert_eq!(system.created(&alice), None);
+        system.inc_providers(&alice);
+        assert_eq!(system.created(&alice), Some(6));
+        system.inc_block_number();
+        system.inc_providers(&alice);
+        assert_eq!(system.inc_consumers(&alice), Ok(()));
+        assert_eq!(system.dec_providers(&alice), Ok(()));
+        assert!(!system.can_dec_providers(&alice));
+        assert_eq!(system.dec_providers(&alice), Err(crate::support::DispatchError::ConsumerRemaining));
+        system.dec_consumers(&alice);
+        assert_eq!((system.providers(&alice), system.consumers(&alice)), (1, 0));
+        assert_eq!(system.created(&alice), Some(6));
+        assert_eq!(system.dec_providers(&alice), Ok(()));
+        // The account was reaped entirely, and is created again in a later block.
+        assert_eq!((system.providers(&alice), system.nonce(&alice), system.created(&alice)), (0, 0, None));
+        system.inc_providers(&alice);
+        assert_eq!(system.created(&alice), Some(7));
+	}
+
+	#[test]
+	fn ensure_origin() {
+		use super::{ensure_root, ensure_signed};
+		use crate::support::{DispatchError, Origin};
+
+		assert_eq!(ensure_signed(Origin::Signed("alice")), Ok("alice"));
+		assert_eq!(ensure_signed(Origin::<&str>::Root), Err(DispatchError::BadOrigin));
+		assert_eq!(ensure_root(Origin::<&str>::Root), Ok(()));
+		assert_eq!(ensure_root(Origin::Signed("alice")), Err(DispatchError::BadOrigin));
+		assert_eq!(ensure_root(Origin::<&str>::None), Err(DispatchError::BadOrigin));
 	}
 }
\ No newline at end of file
@@ -0,0 +1,379 @@
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use crate::codec::{Decode, Encode};
+use crate::storage::{Storage, StorageValue, ValueQuery};
+use crate::support::{
+	DispatchError, DispatchNested, DispatchResult, DispatchResultWithPostInfo, GenesisError, GetDispatchInfo, Instance,
+	Origin, Weight,
+};
+
+/// A pallet which only exists to test how the runtime runs the hooks of its pallets. Every hook
+/// deposits an event with the weight used so far in the block, so the order in which the hooks of
+/// different pallets run can be told from their events. It can also hand calls over to the runtime,
+/// like `sudo` does.
+pub trait Config<I: Instance = ()>: crate::system::Config {
+	/// The calls of the runtime, which the pallet can hand over.
+	type RuntimeCall: Debug + Clone + Encode + Decode + GetDispatchInfo;
+}
+
+/// The events emitted by the test pallet, one for each hook.
+#[macros::event(generate_deposit)]
+#[derive(Debug, macros::CloneNoBound, PartialEq)]
+pub enum Event<T: Config<I>, I: Instance = ()> {
+	/// `on_initialize` ran at the start of block `n`, once `block_weight` was used.
+	Initialized { n: T::BlockNumber, block_weight: Weight },
+	/// `on_idle` ran with `remaining_weight` left in the block.
+	Idle { remaining_weight: Weight },
+	/// `on_finalize` ran at the end of block `n`, once `block_weight` was used.
+	Finalized { n: T::BlockNumber, block_weight: Weight },
+	/// A call handed over by the pallet was dispatched, and returned `result`.
+	Dispatched { result: DispatchResult },
+	/// Never emitted: it only ties the events to the instance of the pallet.
+	#[allow(dead_code)]
+	Instance { instance: PhantomData<I> },
+}
+
+/// The errors returned by the test pallet. Its calls cannot fail, so there are none.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {}
+
+/// The genesis state of the test pallet, which sets the weight its hooks use.
+#[derive(Debug, macros::CloneNoBound, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisConfig<T: Config<I>, I: Instance = ()> {
+	/// The weight used by `on_initialize`.
+	pub initialize_weight: Weight,
+	/// The weight `on_idle` uses, if that much is left in the block.
+	pub idle_weight: Weight,
+	#[serde(skip)]
+	pub _marker: PhantomData<(T, I)>,
+}
+
+impl<T: Config<I>, I: Instance> Default for GenesisConfig<T, I> {
+	fn default() -> Self {
+		Self { initialize_weight: 0, idle_weight: 0, _marker: PhantomData }
+	}
+}
+
+const PALLET: &str = "TestPallet";
+
+#[macros::storage]
+#[derive(Debug)]
+pub struct Pallet<T: Config<I>, I: Instance = ()> {
+	/// The storage handle, through which this pallet deposits its events.
+	storage: Storage,
+	/// The weight used by `on_initialize`.
+	initialize_weight: StorageValue<Weight, ValueQuery>,
+	/// The weight `on_idle` uses, if that much is left in the block.
+	idle_weight: StorageValue<Weight, ValueQuery>,
+	/// Whether `try_state` fails.
+	corrupt: StorageValue<bool, ValueQuery>,
+	/// The call which the runtime has yet to dispatch with root privileges.
+	nested_call: Option<T::RuntimeCall>,
+	/// The results of the calls it handed over, which outlive the rollback of the changes, like
+	/// anything a pallet keeps outside the storage.
+	nested_results: Vec<DispatchResult>,
+	_marker: PhantomData<(T, I)>,
+}
+
+impl<T: Config<I>, I: Instance> Pallet<T, I> {
+	/// Build the genesis state of the pallet.
+	pub fn build_genesis(&mut self, config: &GenesisConfig<T, I>) -> Result<(), GenesisError> {
+		self.initialize_weight.put(&config.initialize_weight);
+		self.idle_weight.put(&config.idle_weight);
+		Ok(())
+	}
+
+	/// The weight used so far in the current block, as recorded by the system pallet.
+	fn block_weight(&self) -> Weight {
+		crate::system::Pallet::<T>::new(self.storage.clone()).block_weight()
+	}
+}
+
+impl<T: Config<I>, I: Instance> crate::support::Hooks<T::BlockNumber> for Pallet<T, I> {
+	fn on_initialize(&mut self, n: T::BlockNumber) -> Weight {
+		self.deposit_event(Event::Initialized { n, block_weight: self.block_weight() });
+		self.initialize_weight.get()
+	}
+
+	fn on_idle(&mut self, remaining_weight: Weight) -> Weight {
+		self.deposit_event(Event::Idle { remaining_weight });
+		self.idle_weight.get().min(remaining_weight)
+	}
+
+	fn on_finalize(&mut self, n: T::BlockNumber) {
+		self.deposit_event(Event::Finalized { n, block_weight: self.block_weight() });
+	}
+
+	fn try_state(&self) -> Result<(), &'static str> {
+		if self.corrupt.get() {
+			return Err("the pallet was corrupted")
+		}
+		Ok(())
+	}
+}
+
+// The calls handed over by `dispatch_as_root` are dispatched by the runtime, which reports their
+// result back.
+impl<T: Config<I>, I: Instance> DispatchNested<T::RuntimeOrigin, T::RuntimeCall> for Pallet<T, I> {
+	fn take_nested_call(&mut self) -> Option<(T::RuntimeOrigin, T::RuntimeCall)> {
+		self.nested_call.take().map(|call| (Origin::Root.into(), call))
+	}
+
+	fn on_nested_result(&mut self, _origin: T::RuntimeOrigin, result: DispatchResultWithPostInfo) {
+		let result = result.map(|_| ()).map_err(|error| error.error);
+		self.nested_results.push(result);
+		self.deposit_event(Event::Dispatched { result });
+	}
+}
+
+// The test runtime only makes calls with unsigned extrinsics, which pay no fee.
+#[macros::call]
+impl<T: Config<I>, I: Instance> Pallet<T, I> {
+	/// Do nothing, using `weight`.
+	#[call_index(0)]
+	#[weight(*weight)]
+	#[allow_unsigned]
+	// The weight is all there is to the call.
+	#[allow(unused_variables)]
+	pub fn consume(&mut self, _origin: T::RuntimeOrigin, weight: Weight) -> DispatchResult {
+		Ok(())
+	}
+
+	/// Make `try_state` fail from now on.
+	#[call_index(1)]
+	#[weight(0)]
+	#[allow_unsigned]
+	pub fn corrupt(&mut self, _origin: T::RuntimeOrigin) -> DispatchResult {
+		self.corrupt.put(&true);
+		Ok(())
+	}
+
+	/// Hand `call` over to the runtime, to dispatch with root privileges, and then fail if `fail`.
+	#[call_index(2)]
+	#[weight(call.get_dispatch_info().weight)]
+	#[allow_unsigned]
+	// Calls are boxed, since the calls of the runtime include this one.
+	#[allow(clippy::boxed_local)]
+	pub fn dispatch_as_root(&mut self, _origin: T::RuntimeOrigin, call: Box<T::RuntimeCall>, fail: bool) -> DispatchResult {
+		self.nested_call = Some(*call);
+		if fail {
+			return Err(DispatchError::Other("the call failed after handing over a call"))
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::storage;
+	use crate::support::{self, BlockError, Dispatch, Instance1, Origin};
+	use crate::system::{self, Phase};
+
+	mod types {
+		pub type AccountId = crate::crypto::Public;
+		pub type Balance = u128;
+		pub type Nonce = u32;
+		pub type BlockNumber = u32;
+		pub type Signature = crate::crypto::Signature;
+		pub type Extrinsic =
+			crate::support::Extrinsic<AccountId, super::RuntimeCall, Nonce, BlockNumber, Balance, Signature>;
+		pub type Header = crate::support::Header<BlockNumber>;
+		pub type Block = crate::support::Block<Header, Extrinsic>;
+	}
+
+	// The `test_pallet` module is what the runtime refers to its pallets by.
+	use super as test_pallet;
+
+	// A runtime with two instances of the test pallet, `first` declared before `second`.
+	#[derive(Debug)]
+	#[macros::runtime]
+	pub struct Runtime {
+		system: system::Pallet<Self>,
+		#[pallet_index(1)]
+		first: test_pallet::Pallet<Self>,
+		#[pallet_index(2)]
+		second: test_pallet::Pallet<Self, Instance1>,
+	}
+
+	impl system::Config for Runtime {
+		type AccountId = types::AccountId;
+		type BlockNumber = types::BlockNumber;
+		type Nonce = types::Nonce;
+		type Signature = types::Signature;
+		type RuntimeOrigin = Origin<types::AccountId>;
+		type RuntimeEvent = RuntimeEvent;
+		type PalletInfo = Self;
+		const BLOCK_WEIGHT_LIMIT: support::Weight = 1_000;
+	}
+
+	impl test_pallet::Config for Runtime {
+		type RuntimeCall = RuntimeCall;
+	}
+
+	impl test_pallet::Config<Instance1> for Runtime {
+		type RuntimeCall = RuntimeCall;
+	}
+
+	// Only unsigned extrinsics are used, which pay no fee.
+	impl support::ChargeTransaction for Runtime {
+		type AccountId = types::AccountId;
+		type Balance = types::Balance;
+
+		fn withdraw_fee(
+			&mut self,
+			_who: &types::AccountId,
+			_info: &support::DispatchInfo,
+			_len: u32,
+			_tip: types::Balance,
+		) -> Option<types::Balance> {
+			Some(0)
+		}
+
+		fn can_pay_fee(
+			&self,
+			_who: &types::AccountId,
+			_info: &support::DispatchInfo,
+			_len: u32,
+			_tip: types::Balance,
+		) -> bool {
+			true
+		}
+
+		fn settle_fee(&mut self, _who: &types::AccountId, _settlement: &support::FeeSettlement<types::Balance>) {}
+	}
+
+	/// A runtime whose `first` pallet uses `first` as the weight of its `on_initialize` and
+	/// `on_idle` hooks, and likewise for `second`.
+	fn new_runtime(first: (support::Weight, support::Weight), second: (support::Weight, support::Weight)) -> Runtime {
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		let mut genesis = RuntimeGenesisConfig::default();
+		(genesis.first.initialize_weight, genesis.first.idle_weight) = first;
+		(genesis.second.initialize_weight, genesis.second.idle_weight) = second;
+		runtime.build_genesis(&genesis).unwrap();
+		runtime
+	}
+
+	fn unsigned(call: RuntimeCall) -> types::Extrinsic {
+		support::Extrinsic { signature: None, call }
+	}
+
+	/// The phase of every event the test pallets deposited in the current block, with the name of
+	/// the pallet which deposited it, and the event.
+	fn hook_events(runtime: &Runtime) -> Vec<(Phase, &'static str, String)> {
+		runtime
+			.system
+			.events()
+			.iter()
+			.filter_map(|record| match &record.event {
+				RuntimeEvent::first(event) => Some((record.phase, "first", format!("{:?}", event))),
+				RuntimeEvent::second(event) => Some((record.phase, "second", format!("{:?}", event))),
+				RuntimeEvent::system(_) => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn hooks_run_in_declaration_order() {
+		let mut runtime = new_runtime((100, 50), (200, 10_000));
+
+		runtime.initialize_block();
+		assert_eq!(
+			hook_events(&runtime),
+			vec![
+				(Phase::Initialization, "first", "Initialized { n: 1, block_weight: 0 }".to_string()),
+				(Phase::Initialization, "second", "Initialized { n: 1, block_weight: 100 }".to_string()),
+			]
+		);
+		assert_eq!(runtime.system.block_weight(), 300);
+
+		// Each pallet is left the weight the pallets before it did not use, and cannot use more.
+		// The events of a phase are collected pallet by pallet, but the weights show that every
+		// `on_idle` hook ran before any `on_finalize` hook.
+		runtime.finalize_block();
+		assert_eq!(
+			hook_events(&runtime)[2..],
+			[
+				(Phase::Finalization, "first", "Idle { remaining_weight: 700 }".to_string()),
+				(Phase::Finalization, "first", "Finalized { n: 1, block_weight: 1000 }".to_string()),
+				(Phase::Finalization, "second", "Idle { remaining_weight: 650 }".to_string()),
+				(Phase::Finalization, "second", "Finalized { n: 1, block_weight: 1000 }".to_string()),
+			]
+		);
+		assert_eq!(runtime.system.block_weight(), 1_000);
+	}
+
+	#[test]
+	fn on_initialize_weight_counts_against_the_block_limit() {
+		let mut runtime = new_runtime((600, 0), (300, 0));
+
+		runtime.initialize_block();
+		let consume = |weight| unsigned(RuntimeCall::first(test_pallet::Call::consume { weight }));
+		assert_eq!(
+			runtime.apply_extrinsic(0, consume(101)),
+			Err(BlockError::ExhaustsResources { extrinsic_index: 0 })
+		);
+		assert_eq!(runtime.apply_extrinsic(0, consume(100)), Ok(Ok(())));
+
+		// `on_idle` gets what the extrinsics left, which is nothing.
+		runtime.finalize_block();
+		let idle = (Phase::Finalization, "first", "Idle { remaining_weight: 0 }".to_string());
+		assert!(hook_events(&runtime).contains(&idle));
+	}
+
+	#[test]
+	fn calls_handed_over_by_failed_calls_are_discarded() {
+		let mut runtime = new_runtime((0, 0), (0, 0));
+		runtime.initialize_block();
+		let dispatch_as_root = |call, fail| {
+			RuntimeCall::first(test_pallet::Call::dispatch_as_root { call: Box::new(call), fail })
+		};
+		let corrupt = RuntimeCall::second(test_pallet::Call::corrupt {});
+		let consume = RuntimeCall::first(test_pallet::Call::consume { weight: 0 });
+		let error = support::DispatchError::Other("the call failed after handing over a call");
+
+		// The call fails after handing over its call, which is never dispatched, and is not left
+		// behind for the next call to hand over.
+		let failing = dispatch_as_root(corrupt.clone(), true);
+		assert_eq!(runtime.apply_extrinsic(0, unsigned(failing.clone())), Ok(Err(error)));
+		assert!(runtime.first.nested_call.is_none());
+		assert!(runtime.first.nested_results.is_empty());
+		assert_eq!(runtime.apply_extrinsic(1, unsigned(dispatch_as_root(consume, false))), Ok(Ok(())));
+		assert!(!runtime.second.corrupt.get());
+
+		// A call which fails inside a call which succeeds is reported once.
+		assert_eq!(runtime.apply_extrinsic(2, unsigned(dispatch_as_root(failing, false))), Ok(Ok(())));
+		assert!(!runtime.second.corrupt.get());
+		assert_eq!(
+			hook_events(&runtime)[2..],
+			[
+				(Phase::ApplyExtrinsic(1), "first", "Dispatched { result: Ok(()) }".to_string()),
+				(Phase::ApplyExtrinsic(2), "first", format!("Dispatched {{ result: Err({:?}) }}", error)),
+			]
+		);
+
+		assert_eq!(runtime.apply_extrinsic(3, unsigned(dispatch_as_root(corrupt, false))), Ok(Ok(())));
+		assert!(runtime.second.corrupt.get());
+		assert_eq!(runtime.first.nested_results, vec![Ok(()), Err(error), Ok(())]);
+	}
+
+	#[test]
+	#[should_panic(expected = "the state of `second` is corrupt: the pallet was corrupted")]
+	fn try_state_failures_surface() {
+		let mut runtime = new_runtime((0, 0), (0, 0));
+		let extrinsics = vec![unsigned(RuntimeCall::second(test_pallet::Call::corrupt {}))];
+
+		// Build the block the way the block builder does, and then import it.
+		let storage = runtime.system.storage().clone();
+		storage.start_transaction();
+		let parent_hash = runtime.initialize_block();
+		assert_eq!(runtime.apply_extrinsic(0, extrinsics[0].clone()), Ok(Ok(())));
+		runtime.finalize_block();
+		let header = runtime.build_header(parent_hash, &extrinsics);
+		storage.rollback_transaction();
+		runtime.reset_events();
+
+		let _ = runtime.execute_block(types::Block { header, extrinsics });
+	}
+}
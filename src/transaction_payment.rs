@@ -0,0 +1,254 @@
+use num::traits::{CheckedAdd, CheckedMul, Saturating, Zero};
+use crate::balances;
+use crate::storage::{Storage, StorageValue, ValueQuery};
+use crate::support::{DispatchInfo, ExistenceRequirement, FeeSettlement, GenesisError, Weight, WithdrawReasons};
+
+pub trait Config: balances::Config<Balance: CheckedMul + From<u64>> {}
+
+/// The events emitted by the transaction payment pallet.
+#[macros::event(generate_deposit)]
+#[derive(Debug, macros::CloneNoBound, PartialEq)]
+pub enum Event<T: Config> {
+	/// `who` paid `actual_fee` for an extrinsic, of which `tip` was a tip.
+	TransactionFeePaid { who: T::AccountId, actual_fee: T::Balance, tip: T::Balance },
+	/// `amount` of a fee was burned, because there is no treasury or it could not hold the fee.
+	FeeBurned { amount: T::Balance },
+}
+
+/// The errors returned by the transaction payment pallet. It has no calls, so there are none.
+#[macros::error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {}
+
+/// The genesis state of the transaction payment pallet, which sets the fees of the chain.
+#[derive(Debug, macros::CloneNoBound, serde::Deserialize)]
+#[serde(
+	bound(deserialize = "T::AccountId: serde::Deserialize<'de>, T::Balance: serde::Deserialize<'de>"),
+	deny_unknown_fields
+)]
+pub struct GenesisConfig<T: Config> {
+	/// The fee paid for every extrinsic.
+	pub base_fee: T::Balance,
+	/// The fee paid for every unit of weight used by the call of an extrinsic.
+	pub weight_fee: T::Balance,
+	/// The fee paid for every byte of an extrinsic.
+	pub length_fee: T::Balance,
+	/// The account which receives the fees. Fees are burned when there is none.
+	pub treasury: Option<T::AccountId>,
+}
+
+impl<T: Config> Default for GenesisConfig<T> {
+	fn default() -> Self {
+		Self {
+			base_fee: 0.into(),
+			weight_fee: 0.into(),
+			length_fee: 0.into(),
+			treasury: None,
+		}
+	}
+}
+
+const PALLET: &str = "TransactionPayment";
+
+/// This pallet charges the callers of signed extrinsics a fee, out of their balance in the
+/// balances pallet. The fee is withdrawn before dispatching the call, and the part of it paid for
+/// weight the call did not use is refunded afterwards.
+#[macros::storage]
+#[derive(Debug)]
+pub struct Pallet<T: Config> {
+	/// A handle to the storage, which keeps the events of this pallet until the runtime takes them.
+	storage: Storage,
+	/// The fee paid for every extrinsic.
+	#[default(0.into())]
+	base_fee: StorageValue<T::Balance, ValueQuery>,
+	/// The fee paid for every unit of weight used by the call of an extrinsic.
+	#[default(0.into())]
+	weight_fee: StorageValue<T::Balance, ValueQuery>,
+	/// The fee paid for every byte of an extrinsic.
+	#[default(0.into())]
+	length_fee: StorageValue<T::Balance, ValueQuery>,
+	/// The account which receives the fees. Fees are burned when there is none.
+	treasury: StorageValue<T::AccountId>,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Build the genesis state of the pallet.
+	pub fn build_genesis(&mut self, config: &GenesisConfig<T>) -> Result<(), GenesisError> {
+		self.base_fee.put(&config.base_fee);
+		self.weight_fee.put(&config.weight_fee);
+		self.length_fee.put(&config.length_fee);
+		if let Some(treasury) = &config.treasury {
+			self.treasury.put(treasury);
+		}
+		Ok(())
+	}
+
+	/// The account which receives the fees, if they are not burned.
+	pub fn treasury(&self) -> Option<T::AccountId> {
+		self.treasury.get()
+	}
+
+	/// The fee of an extrinsic of `len` bytes whose call uses `weight`, including its `tip`.
+	/// Returns `None` if the fee overflows.
+	pub fn compute_fee(&self, len: u32, weight: Weight, tip: T::Balance) -> Option<T::Balance> {
+		let weight_fee = self.weight_fee.get().checked_mul(&weight.into())?;
+		let length_fee = self.length_fee.get().checked_mul(&u64::from(len).into())?;
+		self.base_fee.get().checked_add(&weight_fee)?.checked_add(&length_fee)?.checked_add(&tip)
+	}
+
+	/// Withdraw the fee of an extrinsic from `who`, assuming its call uses all of its declared
+	/// weight. Returns the amount withdrawn, or `None` if `who` cannot pay it.
+	pub fn withdraw_fee(
+		&mut self,
+		balances: &mut balances::Pallet<T>,
+		who: &T::AccountId,
+		info: &DispatchInfo,
+		len: u32,
+		tip: T::Balance,
+	) -> Option<T::Balance> {
+		let fee = self.compute_fee(len, info.weight, tip)?;
+		// The fee is burned, and minted again where it goes once the call is dispatched.
+		balances.burn_from(who, fee, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive).ok()?;
+		Some(fee)
+	}
+
+	/// Whether `who` can pay the fee `withdraw_fee` would withdraw, without withdrawing it.
+	pub fn can_pay_fee(
+		&self,
+		balances: &balances::Pallet<T>,
+		who: &T::AccountId,
+		info: &DispatchInfo,
+		len: u32,
+		tip: T::Balance,
+	) -> bool {
+		self.compute_fee(len, info.weight, tip).is_some_and(|fee| {
+			fee <= balances.reducible_balance(who, WithdrawReasons::Fee, ExistenceRequirement::KeepAlive)
+		})
+	}
+
+	/// Refund `who` for the weight its call did not use out of the amount it paid, and move the
+	/// rest to the treasury.
+	pub fn settle_fee(
+		&mut self,
+		balances: &mut balances::Pallet<T>,
+		who: &T::AccountId,
+		settlement: &FeeSettlement<T::Balance>,
+	) {
+		let FeeSettlement { info, post_info, len, tip, paid } = *settlement;
+		// The actual weight is never higher than the declared weight, so the actual fee is never
+		// higher than the fee paid.
+		let actual_weight = post_info.calc_actual_weight(&info);
+		let actual_fee = self.compute_fee(len, actual_weight, tip).map_or(paid, |fee| fee.min(paid));
+		let refund = paid.saturating_sub(actual_fee);
+		// `who` may not be able to hold the refund if its balance grew during the call, in which
+		// case it pays the full fee. Likewise, fees the treasury cannot hold are burned.
+		let actual_fee = if balances.mint_into(who, refund).is_ok() { actual_fee } else { paid };
+		let deposited = self.treasury().is_some_and(|treasury| balances.mint_into(&treasury, actual_fee).is_ok());
+		self.deposit_event(Event::TransactionFeePaid { who: who.clone(), actual_fee, tip });
+		if !deposited && !actual_fee.is_zero() {
+			self.deposit_event(Event::FeeBurned { amount: actual_fee });
+		}
+	}
+}
+
+// Fees are charged per extrinsic, so there is no per-block logic.
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {}
+
+// The transaction payment pallet does not dispatch other calls.
+impl<T: Config, O, C> crate::support::DispatchNested<O, C> for Pallet<T> {}
+
+// The pallet has no calls: fees are charged by the runtime through `support::ChargeTransaction`.
+#[macros::call]
+impl<T: Config> Pallet<T> {}
+
+#[cfg(test)]
+mod tests {
+	use crate::support::{DispatchInfo, FeeSettlement, Origin, PostDispatchInfo};
+
+	#[derive(Debug, PartialEq)]
+	struct TestConfig;
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::support::TestSignature<String>;
+		type RuntimeOrigin = Origin<String>;
+		type RuntimeEvent = ();
+		type PalletInfo = ();
+		const BLOCK_WEIGHT_LIMIT: crate::support::Weight = u64::MAX;
+	}
+
+	impl crate::balances::Config for TestConfig {
+		type Balance = u128;
+		const EXISTENTIAL_DEPOSIT: u128 = 1;
+	}
+
+	impl super::Config for TestConfig {}
+
+	fn new_pallets(
+		treasury: Option<&str>,
+	) -> (super::Pallet<TestConfig>, crate::balances::Pallet<TestConfig>) {
+		let storage = crate::storage::Storage::in_memory();
+		let mut payment = super::Pallet::<TestConfig>::new(storage.clone());
+		let config = super::GenesisConfig::<TestConfig> {
+			base_fee: 100,
+			weight_fee: 2,
+			length_fee: 10,
+			treasury: treasury.map(str::to_string),
+		};
+		payment.build_genesis(&config).unwrap();
+		let mut balances = crate::balances::Pallet::<TestConfig>::new(storage);
+		balances.force_set_balance(Origin::Root, "alice".to_string(), 1_000).unwrap();
+		(payment, balances)
+	}
+
+	#[test]
+	fn fees_are_charged_and_refunded() {
+		let (mut payment, mut balances) = new_pallets(Some("treasury"));
+		let alice = "alice".to_string();
+		let info = DispatchInfo { weight: 100, allow_unsigned: false };
+		assert_eq!(payment.compute_fee(10, 100, 5), Some(100 + 200 + 100 + 5));
+		assert_eq!(payment.compute_fee(0, u64::MAX, u128::MAX), None);
+
+		// Alice cannot pay for a call which is too heavy.
+		assert!(!payment.can_pay_fee(&balances, &alice, &DispatchInfo { weight: 500, allow_unsigned: false }, 10, 0));
+		assert!(payment.can_pay_fee(&balances, &alice, &info, 10, 5));
+		assert_eq!(payment.withdraw_fee(&mut balances, &alice, &DispatchInfo { weight: 500, allow_unsigned: false }, 10, 0), None);
+		assert_eq!(balances.free_balance(&alice), 1_000);
+
+		let paid = payment.withdraw_fee(&mut balances, &alice, &info, 10, 5).unwrap();
+		assert_eq!(paid, 405);
+		assert_eq!(balances.free_balance(&alice), 595);
+
+		// The call only used 40 of its 100 weight.
+		let post_info = PostDispatchInfo { actual_weight: Some(40) };
+		payment.settle_fee(&mut balances, &alice, &FeeSettlement { info, post_info, len: 10, tip: 5, paid });
+		assert_eq!(balances.free_balance(&alice), 715);
+		assert_eq!(balances.free_balance(&"treasury".to_string()), 285);
+		assert_eq!(
+			payment.take_events(),
+			vec![super::Event::TransactionFeePaid { who: alice, actual_fee: 285, tip: 5 }]
+		);
+	}
+
+	#[test]
+	fn fees_are_burned_without_treasury() {
+		let (mut payment, mut balances) = new_pallets(None);
+		let alice = "alice".to_string();
+		let info = DispatchInfo { weight: 100, allow_unsigned: false };
+		let paid = payment.withdraw_fee(&mut balances, &alice, &info, 10, 0).unwrap();
+		let post_info = PostDispatchInfo::default();
+		payment.settle_fee(&mut balances, &alice, &FeeSettlement { info, post_info, len: 10, tip: 0, paid });
+		assert_eq!(balances.free_balance(&alice), 600);
+		assert_eq!(balances.total_issuance(), 600);
+		assert_eq!(payment.treasury(), None);
+		assert_eq!(
+			payment.take_events(),
+			vec![
+				super::Event::TransactionFeePaid { who: alice, actual_fee: 400, tip: 0 },
+				super::Event::FeeBurned { amount: 400 },
+			]
+		);
+	}
+}
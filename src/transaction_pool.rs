@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+
+use crate::codec::{self, decode_all};
+use crate::support::{self, GetDispatchInfo, Verify};
+use crate::system::Config as _;
+use crate::{types, Runtime};
+
+/// The reason why an extrinsic was not accepted into the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolError {
+	/// The genesis state of the runtime is not built yet, so there is no chain to submit to.
+	NoGenesis,
+	/// The extrinsic could not be decoded.
+	Decode(codec::Error),
+	/// The extrinsic is not signed. There is no way to order or expire unsigned extrinsics.
+	Unsigned,
+	/// The signature of the extrinsic is not valid for its caller.
+	BadSignature,
+	/// The checkpoint of the extrinsic is not a block of the chain, or its caller did not exist yet
+	/// at that block.
+	BadCheckpoint,
+	/// The nonce of the extrinsic is lower than the nonce of its caller: it was already included.
+	StaleNonce,
+	/// An extrinsic with the same caller and nonce is already in the pool.
+	AlreadyImported,
+	/// The caller cannot pay the fee of the extrinsic.
+	CannotPayFees,
+	/// The weight of the extrinsic exceeds the weight limit of a block, so it never fits in one.
+	ExhaustsResources,
+	/// The nonce of the extrinsic is too far ahead of the nonce of its caller to be kept waiting.
+	NonceGapTooLarge,
+	/// The pool holds as many extrinsics as it can.
+	PoolFull,
+}
+
+type Key = (types::AccountId, types::Nonce);
+
+struct Entry {
+	extrinsic: types::Extrinsic,
+	/// The tip paid by the extrinsic. Extrinsics with a higher tip go first.
+	tip: types::Balance,
+	/// The order in which the extrinsic was submitted. Extrinsics submitted first go first.
+	submitted: u64,
+}
+
+/// A pool of signed extrinsics which are waiting to be included in a block.
+///
+/// An extrinsic is ready when its nonce is the nonce of its caller, or follows a ready extrinsic of
+/// the same caller. Any other extrinsic is kept in the future queue until the extrinsics before it
+/// arrive, or are included in a block.
+///
+/// The pool holds at most `capacity` extrinsics, and only keeps extrinsics whose nonce is at most
+/// `max_nonce_gap` ahead of the nonce of their caller, so that nobody can fill it with extrinsics
+/// which are never ready.
+pub struct TransactionPool {
+	ready: BTreeMap<Key, Entry>,
+	future: BTreeMap<Key, Entry>,
+	submitted: u64,
+	capacity: usize,
+	max_nonce_gap: types::Nonce,
+}
+
+impl Default for TransactionPool {
+	fn default() -> Self {
+		Self {
+			ready: BTreeMap::new(),
+			future: BTreeMap::new(),
+			submitted: 0,
+			capacity: Self::CAPACITY,
+			max_nonce_gap: Self::MAX_NONCE_GAP,
+		}
+	}
+}
+
+impl TransactionPool {
+	/// The default number of extrinsics the pool holds.
+	pub const CAPACITY: usize = 4096;
+	/// The default number of nonces an extrinsic may be ahead of the nonce of its caller.
+	pub const MAX_NONCE_GAP: types::Nonce = 16;
+
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Validate the encoded extrinsic against the current state of `runtime`, and add it to the
+	/// pool.
+	pub fn submit(&mut self, runtime: &Runtime, encoded: &[u8]) -> Result<(), PoolError> {
+		let genesis_hash = runtime.system.genesis_hash().ok_or(PoolError::NoGenesis)?;
+		let extrinsic = decode_all::<types::Extrinsic>(encoded).map_err(PoolError::Decode)?;
+		let Some(signature) = &extrinsic.signature else { return Err(PoolError::Unsigned) };
+		let checkpoint_hash = runtime.system.block_hash(&signature.checkpoint).ok_or(PoolError::BadCheckpoint)?;
+		let payload = support::signing_payload(
+			&extrinsic.call,
+			&signature.nonce,
+			&signature.tip,
+			&genesis_hash,
+			&checkpoint_hash,
+		);
+		if !signature.signature.verify(&payload, &signature.caller) {
+			return Err(PoolError::BadSignature)
+		}
+		if runtime.system.created(&signature.caller).is_none_or(|created| created > signature.checkpoint) {
+			return Err(PoolError::BadCheckpoint)
+		}
+		let info = extrinsic.call.get_dispatch_info();
+		if info.weight > Runtime::BLOCK_WEIGHT_LIMIT {
+			return Err(PoolError::ExhaustsResources)
+		}
+		let caller = signature.caller;
+		let nonce = runtime.system.nonce(&caller);
+		if signature.nonce < nonce {
+			return Err(PoolError::StaleNonce)
+		}
+		if signature.nonce - nonce > self.max_nonce_gap {
+			return Err(PoolError::NonceGapTooLarge)
+		}
+		let key = (caller, signature.nonce);
+		if self.ready.contains_key(&key) || self.future.contains_key(&key) {
+			return Err(PoolError::AlreadyImported)
+		}
+		if self.ready.len() + self.future.len() >= self.capacity {
+			return Err(PoolError::PoolFull)
+		}
+		if !support::ChargeTransaction::can_pay_fee(runtime, &caller, &info, encoded.len() as u32, signature.tip) {
+			return Err(PoolError::CannotPayFees)
+		}
+
+		self.submitted += 1;
+		let tip = signature.tip;
+		self.future.insert(key, Entry { extrinsic, tip, submitted: self.submitted });
+		self.promote(&caller, nonce);
+		Ok(())
+	}
+
+	/// Move the extrinsics of `caller` which follow `nonce` without a gap from the future queue to
+	/// the ready queue.
+	fn promote(&mut self, caller: &types::AccountId, mut nonce: types::Nonce) {
+		loop {
+			let key = (*caller, nonce);
+			if !self.ready.contains_key(&key) {
+				let Some(entry) = self.future.remove(&key) else { break };
+				self.ready.insert(key, entry);
+			}
+			nonce += 1;
+		}
+	}
+
+	/// Remove the extrinsics which were included in a block, or can no longer be included, and
+	/// promote the extrinsics which became ready. Called after every block, with the `invalid`
+	/// extrinsics which could not be included in it. The extrinsics of their callers which follow
+	/// them are kept in the future queue.
+	pub fn prune(&mut self, runtime: &Runtime, invalid: &[types::Extrinsic]) {
+		let mut entries = core::mem::take(&mut self.future);
+		entries.append(&mut self.ready);
+		for signature in invalid.iter().filter_map(|extrinsic| extrinsic.signature.as_ref()) {
+			entries.remove(&(signature.caller, signature.nonce));
+		}
+		let mut callers = Vec::new();
+		for ((caller, nonce), entry) in entries {
+			if nonce >= runtime.system.nonce(&caller) {
+				self.future.insert((caller, nonce), entry);
+				callers.push(caller);
+			}
+		}
+		callers.dedup();
+		for caller in callers {
+			self.promote(&caller, runtime.system.nonce(&caller));
+		}
+	}
+
+	/// The best `limit` ready extrinsics to include in the next block, in order.
+	///
+	/// The extrinsics of every caller are included in nonce order. Between callers, extrinsics with
+	/// a higher tip are included first, and those with the same tip in the order they were
+	/// submitted.
+	pub fn best(&self, limit: usize) -> Vec<types::Extrinsic> {
+		// The ready extrinsics of each caller, in nonce order.
+		let mut queues = BTreeMap::<types::AccountId, Vec<&Entry>>::new();
+		for ((caller, _), entry) in &self.ready {
+			queues.entry(*caller).or_default().push(entry);
+		}
+		for queue in queues.values_mut() {
+			queue.reverse();
+		}
+
+		let mut best = Vec::new();
+		while best.len() < limit {
+			let next = queues
+				.iter()
+				.filter_map(|(caller, queue)| {
+					queue.last().map(|entry| (core::cmp::Reverse(entry.tip), entry.submitted, *caller))
+				})
+				.min();
+			let Some((_, _, caller)) = next else { break };
+			let entry = queues.get_mut(&caller).and_then(Vec::pop).expect("found above");
+			best.push(entry.extrinsic.clone());
+		}
+		best
+	}
+
+	/// The number of ready and future extrinsics in the pool.
+	pub fn status(&self) -> (usize, usize) {
+		(self.ready.len(), self.future.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{PoolError, TransactionPool};
+	use crate::block_builder::{BlockBuilder, PushError};
+	use crate::codec::{self, Encode};
+	use crate::support::{BlockError, LockableCurrency, WithdrawReasons};
+	use crate::{
+		balances, crypto, proof_of_existence, signed_extrinsic, storage, support, tipped_extrinsic, types, Runtime,
+		RuntimeCall,
+	};
+
+	fn transfer(to: &crypto::Pair, amount: types::Balance) -> RuntimeCall {
+		RuntimeCall::balances(balances::Call::transfer { to: to.public(), amount })
+	}
+
+	fn new_runtime(endowed: &[&crypto::Pair]) -> Runtime {
+		let mut runtime = Runtime::new(storage::Storage::in_memory());
+		let mut genesis = crate::RuntimeGenesisConfig::default();
+		genesis.balances.balances = endowed.iter().map(|pair| (pair.public(), 100)).collect();
+		genesis.transaction_payment.base_fee = 1;
+		runtime.build_genesis(&genesis).unwrap();
+		runtime
+	}
+
+	#[test]
+	fn submit_validates_extrinsics() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let runtime = new_runtime(&[&alice]);
+		let mut pool = TransactionPool::new();
+
+		assert!(matches!(pool.submit(&runtime, &[1, 2, 3]), Err(PoolError::Decode(_))));
+		let unsigned: types::Extrinsic = support::Extrinsic { signature: None, call: transfer(&bob, 1) };
+		assert_eq!(pool.submit(&runtime, &unsigned.encode()), Err(PoolError::Unsigned));
+		let unbuilt = Runtime::new(storage::Storage::in_memory());
+		let extrinsic = signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 1));
+		assert_eq!(pool.submit(&unbuilt, &extrinsic.encode()), Err(PoolError::NoGenesis));
+
+		let mut forged = signed_extrinsic(&runtime, &bob, 0, transfer(&bob, 1));
+		forged.signature.as_mut().unwrap().caller = alice.public();
+		assert_eq!(pool.submit(&runtime, &forged.encode()), Err(PoolError::BadSignature));
+
+		// Bob does not exist, so he cannot have signed anything since he was created.
+		let extrinsic = signed_extrinsic(&runtime, &bob, 0, transfer(&alice, 0));
+		assert_eq!(pool.submit(&runtime, &extrinsic.encode()), Err(PoolError::BadCheckpoint));
+		let mut extrinsic = signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 1));
+		extrinsic.signature.as_mut().unwrap().checkpoint = 1;
+		assert_eq!(pool.submit(&runtime, &extrinsic.encode()), Err(PoolError::BadCheckpoint));
+		// Alice cannot pay this tip on top of the fee, and must keep the existential deposit.
+		let extrinsic = tipped_extrinsic(&runtime, &alice, 0, 99, transfer(&bob, 1));
+		assert_eq!(pool.submit(&runtime, &extrinsic.encode()), Err(PoolError::CannotPayFees));
+		assert_eq!(runtime.balances.free_balance(&alice.public()), 100);
+
+		// A claim this large never fits in a block.
+		let claim = "x".repeat(10_000);
+		let call = RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim });
+		let extrinsic = signed_extrinsic(&runtime, &alice, 0, call);
+		assert_eq!(pool.submit(&runtime, &extrinsic.encode()), Err(PoolError::ExhaustsResources));
+
+		let extrinsic = signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 1)).encode();
+		assert_eq!(pool.submit(&runtime, &extrinsic), Ok(()));
+		assert_eq!(pool.submit(&runtime, &extrinsic), Err(PoolError::AlreadyImported));
+		assert_eq!(pool.status(), (1, 0));
+	}
+
+	#[test]
+	fn submit_respects_locks() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&[&alice]);
+		let mut pool = TransactionPool::new();
+
+		// Funds locked against paying fees cannot pay them, even though they are free.
+		let id = *b"testlock";
+		runtime.balances.set_lock(id, &alice.public(), 100, WithdrawReasons::Fee).unwrap();
+		let extrinsic = signed_extrinsic(&runtime, &alice, 0, transfer(&bob, 1)).encode();
+		assert_eq!(pool.submit(&runtime, &extrinsic), Err(PoolError::CannotPayFees));
+
+		runtime.balances.set_lock(id, &alice.public(), 100, WithdrawReasons::Transfer).unwrap();
+		assert_eq!(pool.submit(&runtime, &extrinsic), Ok(()));
+	}
+
+	#[test]
+	fn submit_limits_call_depth() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let runtime = new_runtime(&[&alice]);
+		let mut pool = TransactionPool::new();
+		let nested = |depth| {
+			(1..depth).fold(transfer(&bob, 1), |call, _| {
+				RuntimeCall::sudo(crate::sudo::Call::sudo { call: Box::new(call) })
+			})
+		};
+
+		let too_deep = signed_extrinsic(&runtime, &alice, 0, nested(support::MAX_CALL_DEPTH + 1));
+		assert_eq!(pool.submit(&runtime, &too_deep.encode()), Err(PoolError::Decode(codec::Error::TooDeep)));
+		let deepest = signed_extrinsic(&runtime, &alice, 0, nested(support::MAX_CALL_DEPTH));
+		assert_eq!(pool.submit(&runtime, &deepest.encode()), Ok(()));
+
+		// Decoding stops at the limit, however deep the calls claim to be.
+		let sudo_prefix = RuntimeCall::sudo(crate::sudo::Call::sudo { call: Box::new(transfer(&bob, 1)) }).encode();
+		let mut encoded = vec![0];
+		for _ in 0..200_000 {
+			encoded.extend_from_slice(&sudo_prefix[..2]);
+		}
+		assert_eq!(pool.submit(&runtime, &encoded), Err(PoolError::Decode(codec::Error::TooDeep)));
+	}
+
+	#[test]
+	fn ready_and_future_queues() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&[&alice, &bob]);
+		let mut pool = TransactionPool::new();
+		let mut submit = |runtime: &Runtime, pair, nonce| {
+			let extrinsic = signed_extrinsic(runtime, pair, nonce, transfer(&alice, 1));
+			pool.submit(runtime, &extrinsic.encode()).map(|()| pool.status())
+		};
+
+		// Alice's extrinsics wait until the ones before them arrive.
+		assert_eq!(submit(&runtime, &alice, 2), Ok((0, 1)));
+		assert_eq!(submit(&runtime, &bob, 0), Ok((1, 1)));
+		assert_eq!(submit(&runtime, &alice, 1), Ok((1, 2)));
+		assert_eq!(submit(&runtime, &alice, 0), Ok((4, 0)));
+		assert_eq!(submit(&runtime, &alice, 4), Ok((4, 1)));
+
+		// Bob's extrinsic was submitted before any of alice's became ready.
+		let nonces = |extrinsics: &[types::Extrinsic]| {
+			extrinsics
+				.iter()
+				.map(|e| e.signature.as_ref().map(|s| (s.caller == alice.public(), s.nonce)).unwrap())
+				.collect::<Vec<_>>()
+		};
+		let best = pool.best(3);
+		assert_eq!(nonces(&best), vec![(false, 0), (true, 0), (true, 1)]);
+
+		// Included extrinsics are removed, and the rest are kept in order.
+		let block = runtime.build_block(best).unwrap();
+		runtime.execute_block(block).unwrap();
+		pool.prune(&runtime, &[]);
+		assert_eq!(pool.status(), (1, 1));
+		assert_eq!(nonces(&pool.best(10)), vec![(true, 2)]);
+
+		let mut submit = |pair, nonce| {
+			let extrinsic = signed_extrinsic(&runtime, pair, nonce, transfer(&alice, 1));
+			pool.submit(&runtime, &extrinsic.encode())
+		};
+		assert_eq!(submit(&alice, 1), Err(PoolError::StaleNonce));
+		assert_eq!(submit(&alice, 3), Ok(()));
+		assert_eq!(pool.status(), (3, 0));
+	}
+
+	#[test]
+	fn submit_limits_the_pool() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let runtime = new_runtime(&[&alice, &bob]);
+		let mut pool = TransactionPool::new();
+		pool.capacity = 3;
+		pool.max_nonce_gap = 2;
+		let mut submit = |pair, nonce| {
+			let extrinsic = signed_extrinsic(&runtime, pair, nonce, transfer(&alice, 1));
+			pool.submit(&runtime, &extrinsic.encode())
+		};
+
+		assert_eq!(submit(&alice, 3), Err(PoolError::NonceGapTooLarge));
+		assert_eq!(submit(&alice, 2), Ok(()));
+		assert_eq!(submit(&alice, 0), Ok(()));
+		assert_eq!(submit(&bob, 0), Ok(()));
+		assert_eq!(submit(&alice, 1), Err(PoolError::PoolFull));
+		// Extrinsics already in the pool are reported as such, even when it is full.
+		assert_eq!(submit(&bob, 0), Err(PoolError::AlreadyImported));
+		assert_eq!(pool.status(), (2, 1));
+	}
+
+	#[test]
+	fn best_orders_by_tip() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let charlie = crypto::Pair::from_seed("charlie");
+		let runtime = new_runtime(&[&alice, &bob, &charlie]);
+		let mut pool = TransactionPool::new();
+		for (pair, nonce, tip) in [(&alice, 0, 0), (&alice, 1, 50), (&bob, 0, 10), (&charlie, 0, 5), (&charlie, 1, 5)] {
+			let extrinsic = tipped_extrinsic(&runtime, pair, nonce, tip, transfer(&alice, 1));
+			pool.submit(&runtime, &extrinsic.encode()).unwrap();
+		}
+
+		// Alice's second extrinsic has the highest tip, but has to wait for her first one.
+		let order = pool
+			.best(10)
+			.iter()
+			.map(|e| e.signature.as_ref().map(|s| (s.caller, s.nonce)).unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			order,
+			vec![
+				(bob.public(), 0),
+				(charlie.public(), 0),
+				(charlie.public(), 1),
+				(alice.public(), 0),
+				(alice.public(), 1),
+			]
+		);
+	}
+
+	#[test]
+	fn prune_removes_invalid_extrinsics() {
+		let alice = crypto::Pair::from_seed("alice");
+		let bob = crypto::Pair::from_seed("bob");
+		let mut runtime = new_runtime(&[&alice]);
+		let mut pool = TransactionPool::new();
+
+		// Both extrinsics can pay their fee on their own, but once the first is included, alice is
+		// left with nothing to pay the second with.
+		for (nonce, amount) in [(0, 98), (1, 1)] {
+			let extrinsic = signed_extrinsic(&runtime, &alice, nonce, transfer(&bob, amount));
+			pool.submit(&runtime, &extrinsic.encode()).unwrap();
+		}
+		let mut builder = BlockBuilder::new(&mut runtime, 10).unwrap();
+		let mut invalid = Vec::new();
+		for extrinsic in pool.best(10) {
+			if let Err(error) = builder.push(extrinsic.clone()) {
+				assert_eq!(error, PushError::Invalid(BlockError::CannotPayFees { extrinsic_index: 1 }));
+				invalid.push(extrinsic);
+			}
+		}
+		let block = builder.finalize();
+		runtime.execute_block(block).unwrap();
+		assert_eq!(invalid.len(), 1);
+
+		// The invalid extrinsic would otherwise stay ready forever.
+		pool.prune(&runtime, &invalid);
+		assert_eq!(pool.status(), (0, 0));
+	}
+}